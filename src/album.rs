@@ -1,27 +1,77 @@
 use super::errors::*;
-use std::time::Duration;
 
-use indicatif::ProgressBar;
-use rand::{seq::SliceRandom, thread_rng};
+use chrono::NaiveDateTime;
+use rand::{seq::SliceRandom, thread_rng, Rng};
 use reqwest::Url;
-use tl::ParserOptions;
+use serde::{Deserialize, Serialize};
+use tl::{Node, ParserOptions};
 
-use crate::network::create_spoof_client;
+use crate::{
+    client::Context,
+    file::{UploadedFile, DATE_FORMAT},
+    filter::FileFilter,
+    progress::{Task, TaskKind},
+};
 
 pub struct Files {
-    pub urls: Vec<Url>,
+    pub files: Vec<UploadedFile>,
 }
 
 impl Files {
+    pub fn urls(&self) -> impl DoubleEndedIterator<Item = &Url> {
+        self.files.iter().map(|x| &x.url)
+    }
+
     pub fn random_file(&self) -> Option<&str> {
         let mut rng = thread_rng();
-        self.urls.choose(&mut rng).map(Url::as_str)
+        self.files.choose(&mut rng).map(|x| x.url.as_str())
+    }
+
+    /// Picks up to `count` distinct files matching `filter`, in random order.
+    ///
+    /// Pass a seeded `rng` for reproducible picks.
+    pub fn random_files(
+        &self,
+        count: usize,
+        filter: &FileFilter,
+        rng: &mut impl Rng,
+    ) -> Vec<&UploadedFile> {
+        let matching = self
+            .files
+            .iter()
+            .filter(|x| filter.matches(x))
+            .collect::<Vec<_>>();
+        matching.choose_multiple(rng, count).copied().collect()
+    }
+
+    /// Fills in the name, size and date of files found in `listing`, matched by slug.
+    ///
+    /// Album pages only show urls, the account listing knows the rest for files you own.
+    pub fn with_metadata(mut self, listing: impl IntoIterator<Item = UploadedFile>) -> Self {
+        let mut listing = listing
+            .into_iter()
+            .map(|x| (x.slug(), x))
+            .collect::<std::collections::HashMap<_, _>>();
+        for file in &mut self.files {
+            if let Some(listed) = listing.remove(&file.slug()) {
+                *file = listed;
+            }
+        }
+        self
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// An album, along with whatever was scraped about it.
+///
+/// Only `url` is known for albums created with `Album::new`, `Album::fetch` fills in the rest.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Album {
     pub url: Url,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// creation date, in the timezone of the catbox server
+    pub created: Option<NaiveDateTime>,
+    pub file_count: Option<usize>,
 }
 
 impl Album {
@@ -35,7 +85,13 @@ impl Album {
     /// let album = Album::new("https://catbox.moe/c/hpxdlu");
     /// ```
     pub fn new(url: impl Into<Url>) -> Self {
-        Self { url: url.into() }
+        Self {
+            url: url.into(),
+            title: None,
+            description: None,
+            created: None,
+            file_count: None,
+        }
     }
 
     /// Fetches the the URLs from the album's webpage.
@@ -43,31 +99,43 @@ impl Album {
     /// This function sends an HTTP GET request to the album's URL, parses the
     /// HTML response, and extracts the URLs of the files embedded within the page.
     ///
-    pub async fn fetch_files(&self) -> Result<Files, AlbumError> {
-        let client = create_spoof_client(None)?;
-
-        let pb = ProgressBar::new_spinner().with_message("Downloading data...");
-        pb.enable_steady_tick(Duration::from_millis(100));
+    pub async fn fetch_files(&self, context: &Context) -> Result<Files, AlbumError> {
+        Ok(self.fetch(context).await?.1)
+    }
 
-        let file = client
-            .get(self.url.clone())
-            .send()
-            .await
-            .map_err(NetworkError::DownloadRequest)?
-            .error_for_status()
-            .map_err(NetworkError::ErrorCode)?
-            .text()
-            .await
-            .map_err(NetworkError::InvalidText)?;
+    /// Fetches the album's webpage, returning the album with its title, description and file
+    /// count filled in, along with its files.
+    pub async fn fetch(&self, context: &Context) -> Result<(Self, Files), AlbumError> {
+        let task = Task::start(
+            context.progress(),
+            TaskKind::Other,
+            "Downloading data...",
+            None,
+        );
 
-        pb.finish_and_clear();
+        let file = task.complete(
+            async {
+                context
+                    .http()
+                    .get(self.url.clone())
+                    .send()
+                    .await
+                    .map_err(NetworkError::DownloadRequest)?
+                    .error_for_status()
+                    .map_err(NetworkError::ErrorCode)?
+                    .text()
+                    .await
+                    .map_err(NetworkError::InvalidText)
+            }
+            .await,
+        )?;
 
         let html =
             tl::parse(&file, ParserOptions::default()).map_err(HtmlParsingError::InvalidHtml)?;
 
         let parser = html.parser();
 
-        let urls = html
+        let files = html
             .get_elements_by_class_name("imagecontainer")
             .next()
             .ok_or(HtmlParsingError::LackOfContainer)?
@@ -93,7 +161,42 @@ impl Album {
             .map(|x| x.map(|x| Url::parse(x).ok()))
             .filter(|x| x.as_ref().is_ok_and(Option::is_some))
             .filter_map(Result::transpose)
+            .map(|x| x.map(UploadedFile::new))
             .collect::<Result<Vec<_>, _>>()?;
-        Ok(Files { urls })
+
+        // the album is described by the first heading and the paragraph below it, ahead of its
+        // files, with the date told apart by what it looks like
+        let (mut title, mut description, mut created) = (None, None, None);
+        for tag in html.nodes().iter().filter_map(Node::as_tag) {
+            if tag.attributes().is_class_member("imagecontainer") {
+                break;
+            }
+            let text = tag.inner_text(parser);
+            let text = text.trim();
+            if text.is_empty() {
+                continue;
+            }
+
+            match tag.name().as_utf8_str().as_ref() {
+                "h1" | "h2" | "h3" if title.is_none() => title = Some(text.to_owned()),
+                "p" if title.is_some() && description.is_none() => {
+                    description = Some(text.to_owned());
+                }
+                _ => {}
+            }
+            if created.is_none() {
+                created = NaiveDateTime::parse_from_str(text, DATE_FORMAT).ok();
+            }
+        }
+
+        let album = Self {
+            url: self.url.clone(),
+            title: title.or_else(|| self.title.clone()),
+            description: description.or_else(|| self.description.clone()),
+            created: created.or(self.created),
+            file_count: Some(files.len()),
+        };
+
+        Ok((album, Files { files }))
     }
 }
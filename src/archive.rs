@@ -0,0 +1,247 @@
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use futures_util::Stream;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use tokio::sync::mpsc;
+use walkdir::WalkDir;
+
+use crate::UploadFileError;
+
+/// How directories are packed when uploaded as a single file.
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveOptions {
+    pub format: ArchiveFormat,
+    pub excludes: Excludes,
+}
+
+/// Size of each chunk sent from the archiver thread to the upload body.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArchiveFormat {
+    #[default]
+    TarZst,
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::TarZst => "tar.zst",
+            Self::Zip => "zip",
+        }
+    }
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "tar.zst" | "tzst" => Ok(Self::TarZst),
+            "zip" => Ok(Self::Zip),
+            s => Err(format!(
+                "invalid archive format `{s}` (expected one of: tar.zst, zip)"
+            )),
+        }
+    }
+}
+
+/// Glob rules deciding which entries of a directory are skipped.
+///
+/// Patterns are matched against the path relative to the walked directory.
+#[derive(Debug, Clone)]
+pub struct Excludes {
+    set: GlobSet,
+}
+
+impl Excludes {
+    pub fn new<T: AsRef<str>>(
+        patterns: impl IntoIterator<Item = T>,
+    ) -> Result<Self, UploadFileError> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let pattern = pattern.as_ref();
+            builder.add(
+                Glob::new(pattern).map_err(|source| UploadFileError::InvalidGlob {
+                    source,
+                    pattern: pattern.to_owned(),
+                })?,
+            );
+        }
+        let set = builder
+            .build()
+            .map_err(|source| UploadFileError::InvalidGlob {
+                source,
+                pattern: String::new(),
+            })?;
+        Ok(Self { set })
+    }
+
+    pub fn is_excluded(&self, relative: impl AsRef<Path>) -> bool {
+        self.set.is_match(relative)
+    }
+}
+
+impl Default for Excludes {
+    fn default() -> Self {
+        Self {
+            set: GlobSet::empty(),
+        }
+    }
+}
+
+/// Collects every regular file below `dir`, skipping excluded entries.
+///
+/// The returned paths are relative to `dir` and sorted, so archives are reproducible.
+pub fn walk_files(dir: &Path, excludes: &Excludes) -> Result<Vec<PathBuf>, UploadFileError> {
+    let mut files = WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.path().strip_prefix(dir).map_or(true, |x| {
+                x.as_os_str().is_empty() || !excludes.is_excluded(x)
+            })
+        })
+        .filter(|entry| entry.as_ref().map_or(true, |x| x.file_type().is_file()))
+        .map(|entry| {
+            let entry = entry.map_err(|source| UploadFileError::WalkDirectory {
+                source,
+                dir: dir.to_path_buf(),
+            })?;
+            Ok(entry
+                .path()
+                .strip_prefix(dir)
+                .unwrap_or(entry.path())
+                .to_path_buf())
+        })
+        .collect::<Result<Vec<_>, UploadFileError>>()?;
+    files.sort();
+    Ok(files)
+}
+
+/// Packs `files`(relative to `dir`) into an archive, streaming the output chunk by chunk.
+///
+/// The archive is written on a blocking thread and never touches the disk. `on_read` is called with
+/// the amount of uncompressed bytes consumed from the source files, which is what progress is measured in.
+pub fn stream_archive(
+    dir: PathBuf,
+    files: Vec<PathBuf>,
+    format: ArchiveFormat,
+    on_read: impl Fn(u64) + Send + 'static,
+) -> impl Stream<Item = io::Result<Vec<u8>>> {
+    let (tx, rx) = mpsc::channel(8);
+
+    tokio::task::spawn_blocking(move || {
+        let mut writer = ChannelWriter {
+            tx: tx.clone(),
+            buf: Vec::with_capacity(CHUNK_SIZE),
+        };
+        let result = write_archive(&dir, &files, format, &mut writer, &on_read)
+            .and_then(|()| writer.flush());
+        if let Err(err) = result {
+            let _ = tx.blocking_send(Err(err));
+        }
+    });
+
+    futures_util::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|x| (x, rx)) })
+}
+
+fn write_archive(
+    dir: &Path,
+    files: &[PathBuf],
+    format: ArchiveFormat,
+    writer: &mut ChannelWriter,
+    on_read: &dyn Fn(u64),
+) -> io::Result<()> {
+    let root = PathBuf::from(dir.file_name().unwrap_or(dir.as_os_str()));
+
+    match format {
+        ArchiveFormat::TarZst => {
+            let encoder = zstd::stream::write::Encoder::new(writer, 0)?;
+            let mut builder = tar::Builder::new(encoder);
+            for relative in files {
+                let path = dir.join(relative);
+                let file = File::open(&path)?;
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata(&file.metadata()?);
+                builder.append_data(
+                    &mut header,
+                    root.join(relative),
+                    CountingReader {
+                        inner: file,
+                        on_read,
+                    },
+                )?;
+            }
+            builder.into_inner()?.finish()?;
+        }
+        ArchiveFormat::Zip => {
+            let mut zip = zip::ZipWriter::new_stream(writer);
+            let options = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated)
+                .large_file(true);
+            for relative in files {
+                let name = root.join(relative).to_string_lossy().replace('\\', "/");
+                zip.start_file(name, options)?;
+                let mut reader = CountingReader {
+                    inner: File::open(dir.join(relative))?,
+                    on_read,
+                };
+                io::copy(&mut reader, &mut zip)?;
+            }
+            zip.finish()?;
+        }
+    }
+    Ok(())
+}
+
+struct CountingReader<'a, R> {
+    inner: R,
+    on_read: &'a dyn Fn(u64),
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        (self.on_read)(n as u64);
+        Ok(n)
+    }
+}
+
+/// Forwards written bytes into the upload body in `CHUNK_SIZE` pieces.
+struct ChannelWriter {
+    tx: mpsc::Sender<io::Result<Vec<u8>>>,
+    buf: Vec<u8>,
+}
+
+impl ChannelWriter {
+    fn send(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.buf, Vec::with_capacity(CHUNK_SIZE));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "upload body was dropped"))
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        if self.buf.len() >= CHUNK_SIZE {
+            self.send()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send()
+    }
+}
@@ -1,40 +1,49 @@
-use std::ops::Deref;
+use std::{ops::Deref, sync::Arc};
 
-use crate::{network::create_spoof_client, NetworkError};
-use reqwest::Client;
+use crate::{client::Context, progress::ProgressReporter, NetworkError};
+use reqwest::Url;
 
 #[derive(Clone)]
 pub struct AuthenticatedClient {
-    client: Client,
+    context: Context,
 }
 
 impl Deref for AuthenticatedClient {
-    type Target = Client;
+    type Target = Context;
 
     fn deref(&self) -> &Self::Target {
-        &self.client
+        &self.context
     }
 }
 
 impl AuthenticatedClient {
-    pub async fn new(username: &str, password: &str) -> Result<Self, NetworkError> {
-        const LOGIN_URL: &str = "https://catbox.moe/user/dologin.php";
-
-        let client = create_spoof_client(None)?;
-
-        client
-            .post(LOGIN_URL)
+    pub async fn new(
+        context: Context,
+        username: &str,
+        password: &str,
+    ) -> Result<Self, NetworkError> {
+        context
+            .http()
+            .post(context.endpoints().login())
             .form(&[("username", username), ("password", password)])
             .send()
             .await
             .map_err(NetworkError::DownloadRequest)?
             .error_for_status()
             .map_err(NetworkError::ErrorCode)?;
-        Ok(Self { client })
+        Ok(Self { context })
+    }
+
+    /// The same session, reporting progress to `progress` instead.
+    pub fn with_progress(&self, progress: Arc<dyn ProgressReporter>) -> Self {
+        Self {
+            context: self.context.with_progress(progress),
+        }
     }
 
-    pub async fn fetch_html(&self, url: &str) -> Result<String, NetworkError> {
-        self.client
+    pub async fn fetch_html(&self, url: Url) -> Result<String, NetworkError> {
+        self.context
+            .http()
             .get(url)
             .send()
             .await
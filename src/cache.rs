@@ -0,0 +1,157 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{Local, NaiveDateTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{album::Album, errors::*, file::UploadedFile};
+
+/// How long listings are used before they are fetched again, unless configured otherwise.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Whose listings are cached, the cache only holds the listings of one account at a time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Owner {
+    /// account page of the site, so listings of self-hosted instances are kept apart
+    pub account: Url,
+    pub username: String,
+}
+
+/// A listing and when it was fetched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cached<T> {
+    /// local time of the fetch
+    pub fetched: NaiveDateTime,
+    pub value: T,
+}
+
+impl<T> Cached<T> {
+    /// A listing fetched just now.
+    pub fn now(value: T) -> Self {
+        Self {
+            fetched: Local::now().naive_local(),
+            value,
+        }
+    }
+
+    /// Whether the listing was fetched less than `ttl` ago.
+    pub fn is_fresh(&self, ttl: Duration) -> bool {
+        TimeDelta::from_std(ttl).is_ok_and(|ttl| Local::now().naive_local() - self.fetched < ttl)
+    }
+}
+
+/// Everything cached of an account, `None` for listings never fetched.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Listing {
+    pub owner: Option<Owner>,
+    pub albums: Option<Cached<Vec<Album>>>,
+    pub files: Option<Cached<Vec<UploadedFile>>>,
+}
+
+/// On-disk cache of the albums and files of an account, so they can be shown without
+/// scraping catbox again.
+///
+/// Lives in `<cache dir>/catbox-cli/listing.json`, unless overridden by `CBX_CACHE`.
+/// Clones share a lock, so concurrent updates from one process don't lose each other.
+#[derive(Debug, Clone)]
+pub struct ListingCache {
+    path: PathBuf,
+    ttl: Duration,
+    lock: Arc<Mutex<()>>,
+}
+
+impl ListingCache {
+    pub fn open() -> Result<Self, CacheError> {
+        if let Some(path) = std::env::var_os("CBX_CACHE") {
+            return Ok(Self::at(path));
+        }
+        Ok(Self::at(
+            dirs::cache_dir()
+                .ok_or(CacheError::LackOfCacheDir)?
+                .join("catbox-cli")
+                .join("listing.json"),
+        ))
+    }
+
+    pub fn at(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            ttl: DEFAULT_TTL,
+            lock: Arc::default(),
+        }
+    }
+
+    /// Listings older than `ttl` are fetched again, `Duration::ZERO` always fetches them.
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The cached listings of `owner`.
+    ///
+    /// A missing or unreadable cache, or one of another account, is an empty one.
+    pub fn load(&self, owner: &Owner) -> Listing {
+        Some(self.load_any())
+            .filter(|x| x.owner.as_ref() == Some(owner))
+            .unwrap_or_else(|| Listing {
+                owner: Some(owner.clone()),
+                ..Listing::default()
+            })
+    }
+
+    /// The cached listings, whichever account they belong to.
+    ///
+    /// For callers that can't afford to find out the account, e.g. shell completions.
+    pub fn load_any(&self) -> Listing {
+        std::fs::read(&self.path)
+            .ok()
+            .and_then(|x| serde_json::from_slice(&x).ok())
+            .unwrap_or_default()
+    }
+
+    /// Changes the cached listings of `owner` with `update`, and writes them back.
+    pub fn update(
+        &self,
+        owner: &Owner,
+        update: impl FnOnce(&mut Listing),
+    ) -> Result<(), CacheError> {
+        let _guard = self
+            .lock
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut listing = self.load(owner);
+        update(&mut listing);
+        self.save(&listing)
+    }
+
+    fn save(&self, listing: &Listing) -> Result<(), CacheError> {
+        let write_error = |source| CacheError::WriteCache {
+            source,
+            path: self.path.clone(),
+        };
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(write_error)?;
+        }
+
+        let json = serde_json::to_vec(listing).expect("listing serializes");
+        // written next to the cache first, so readers never see half a listing
+        let temporary = self.path.with_extension("json.tmp");
+        std::fs::write(&temporary, json)
+            .and_then(|()| std::fs::rename(&temporary, &self.path))
+            .map_err(write_error)
+    }
+}
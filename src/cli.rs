@@ -1,10 +1,26 @@
-use std::path::PathBuf;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
 
-use argh::FromArgs;
+use argh::{ArgsInfo, FromArgs};
+use url::Url;
 
-use crate::upload::LitterExpiry;
+use catbox::{
+    archive::ArchiveFormat,
+    export::ExportFormat,
+    file::parse_size,
+    filter::{parse_date, SortKey},
+    link::LinkFormat,
+    network::TlsBackend,
+    upload::LitterExpiry,
+};
+use chrono::NaiveDateTime;
 
-#[derive(FromArgs, PartialEq, Eq, Debug, Clone)]
+use crate::{completions::Shell, events::OutputFormat, integrations::HookTool};
+
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
 /// Top-level command.
 pub struct Cli {
     #[argh(subcommand)]
@@ -12,19 +28,47 @@ pub struct Cli {
     #[argh(switch, short = 'j')]
     /// whether to output in json
     pub json: bool,
+    #[argh(option)]
+    /// how uploads are reported, `jsonl` prints one json event per line to stdout
+    /// Supported Values: [text, jsonl]
+    pub output: Option<OutputFormat>,
+    #[argh(option)]
+    /// proxy for every request, supports http://, https:// and socks5://
+    /// `none` ignores the HTTP_PROXY/HTTPS_PROXY/ALL_PROXY environment variables
+    pub proxy: Option<String>,
+    #[argh(option)]
+    /// PEM file with extra root certificates to trust
+    pub ca_bundle: Option<PathBuf>,
+    #[argh(option)]
+    /// TLS implementation to use
+    /// Supported Values: [native, rustls]
+    pub tls: Option<TlsBackend>,
+    #[argh(option)]
+    /// main site to talk to instead of catbox.moe, e.g. a self-hosted clone or mock server
+    /// files and Litterbox are served from the same host unless configured otherwise
+    pub base_url: Option<Url>,
+    #[argh(switch)]
+    /// fetch album and file listings from catbox instead of the listing cache
+    pub refresh: bool,
 }
 
-#[derive(FromArgs, PartialEq, Eq, Debug, Clone)]
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
 #[argh(subcommand)]
 pub enum CliSubCommands {
     File(FileCommand),
     Album(AlbumCommand),
     Config(ConfigCommand),
+    Watch(WatchCommand),
+    Integrations(IntegrationsCommand),
+    Serve(ServeCommand),
+    Tui(TuiCommand),
+    Completions(CompletionsCommand),
+    Man(ManCommand),
 }
 
 // Config Commands <------------------>
 
-#[derive(FromArgs, PartialEq, Eq, Debug, Clone)]
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
 /// Controling files.
 #[argh(subcommand, name = "config")]
 pub struct ConfigCommand {
@@ -32,18 +76,20 @@ pub struct ConfigCommand {
     pub command: ConfigSubCommands,
 }
 
-#[derive(FromArgs, PartialEq, Eq, Debug, Clone)]
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
 #[argh(subcommand)]
 pub enum ConfigSubCommands {
     Save(SaveConfig),
     Delete(DeleteConfig),
+    Set(SetConfig),
+    Show(ShowConfig),
 }
-#[derive(FromArgs, PartialEq, Eq, Debug, Clone)]
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
 /// Deletes both your account username and password.
 #[argh(subcommand, name = "delete")]
 pub struct DeleteConfig {}
 
-#[derive(FromArgs, PartialEq, Eq, Debug, Clone)]
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
 /// Saves your account username and password.
 #[argh(subcommand, name = "save")]
 pub struct SaveConfig {
@@ -55,10 +101,28 @@ pub struct SaveConfig {
     pub password: String,
 }
 
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+/// Changes a setting of the config file.
+/// Supported Keys: [strip-metadata, proxy, ca-bundle, tls, base-url, files-url, litterbox-url]
+#[argh(subcommand, name = "set")]
+pub struct SetConfig {
+    #[argh(positional)]
+    /// name of the setting
+    pub key: String,
+    #[argh(positional)]
+    /// new value of the setting
+    pub value: String,
+}
+
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+/// Prints the current config.
+#[argh(subcommand, name = "show")]
+pub struct ShowConfig {}
+
 // <-------------------------------->
 // File Commands <------------------>
 
-#[derive(FromArgs, PartialEq, Eq, Debug, Clone)]
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
 /// Controling files.
 #[argh(subcommand, name = "file")]
 pub struct FileCommand {
@@ -66,18 +130,77 @@ pub struct FileCommand {
     pub command: FileSubCommands,
 }
 
-#[derive(FromArgs, PartialEq, Eq, Debug, Clone)]
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
 #[argh(subcommand)]
 pub enum FileSubCommands {
     Upload(FileUpload),
     List(FileList),
 }
-#[derive(FromArgs, PartialEq, Eq, Debug, Clone)]
-/// Uploading files.
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+/// Lists the files uploaded to your account.
 #[argh(subcommand, name = "list")]
-pub struct FileList {}
+pub struct FileList {
+    #[argh(switch, short = 'l')]
+    /// show the date, size and original name of every file
+    pub long: bool,
+    #[argh(option)]
+    /// list at most this many files
+    pub limit: Option<usize>,
+    #[argh(option)]
+    /// only list the files on this page of your account, counting from 1
+    pub page: Option<u32>,
+    #[argh(option)]
+    /// only list files with this extension, can be repeated
+    pub ext: Vec<String>,
+    #[argh(option)]
+    /// only list files whose name or slug contains this, case insensitive
+    pub name: Option<String>,
+    #[argh(option)]
+    /// only list files whose name or slug matches this regex
+    pub name_regex: Option<String>,
+    #[argh(option, from_str_fn(parse_since))]
+    /// only list files uploaded on or after this date, YYYY-MM-DD[ HH:MM:SS]
+    pub since: Option<NaiveDateTime>,
+    #[argh(option, from_str_fn(parse_until))]
+    /// only list files uploaded on or before this date, YYYY-MM-DD[ HH:MM:SS]
+    pub until: Option<NaiveDateTime>,
+    #[argh(option, from_str_fn(parse_size_arg))]
+    /// only list files of at least this size, e.g. `500KB`
+    pub min_size: Option<u64>,
+    #[argh(option, from_str_fn(parse_size_arg))]
+    /// only list files of at most this size, e.g. `10MB`
+    pub max_size: Option<u64>,
+    #[argh(option)]
+    /// order of the files
+    /// Supported Values: [name, date, size]
+    pub sort: Option<SortKey>,
+    #[argh(switch)]
+    /// reverse the order of the files
+    pub reverse: bool,
+    #[argh(option)]
+    /// print the files as links in this format, images and videos are embedded
+    /// Supported Values: [url, markdown, html, bbcode, template]
+    pub format: Option<LinkFormat>,
+    #[argh(option)]
+    /// print every file with this template, `{{name}}`, `{{url}}`, `{{size}}` and `{{short}}` are replaced
+    pub template: Option<String>,
+}
+
+fn parse_since(value: &str) -> Result<NaiveDateTime, String> {
+    parse_date(value, false)
+}
+
+fn parse_until(value: &str) -> Result<NaiveDateTime, String> {
+    parse_date(value, true)
+}
+
+fn parse_size_arg(value: &str) -> Result<u64, String> {
+    parse_size(value).ok_or_else(|| {
+        format!("invalid size `{value}` (expected a number of bytes, optionally followed by KB, MB, GB or TB)")
+    })
+}
 
-#[derive(FromArgs, PartialEq, Eq, Debug, Clone)]
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
 /// Uploading files.
 #[argh(subcommand, name = "upload")]
 pub struct FileUpload {
@@ -90,14 +213,43 @@ pub struct FileUpload {
     ///
     /// If not provided defaults to `1h`
     pub expiry: Option<LitterExpiry>,
+    #[argh(option)]
+    /// archive format used when a directory is given
+    /// Supported Values: [tar.zst, zip]
+    ///
+    /// If not provided defaults to `tar.zst`
+    pub archive_format: Option<ArchiveFormat>,
+    #[argh(option)]
+    /// glob of paths to skip inside directories, can be repeated
+    pub exclude: Vec<String>,
+    #[argh(switch, short = 'r')]
+    /// upload every file inside given directories individually instead of archiving them
+    pub recursive: bool,
+    #[argh(option)]
+    /// add the uploaded files to this album(the last part of the url)
+    pub album: Option<String>,
+    #[argh(switch)]
+    /// strip EXIF/XMP/ICC metadata from JPEG, PNG and WebP files before uploading
+    pub strip_metadata: bool,
+    #[argh(option)]
+    /// print the uploaded files as links in this format, images and videos are embedded
+    /// Supported Values: [url, markdown, html, bbcode, template]
+    pub format: Option<LinkFormat>,
+    #[argh(option)]
+    /// print the uploaded files with this template, `{{name}}`, `{{url}}`, `{{size}}` and `{{short}}` are replaced
+    pub template: Option<String>,
+    #[argh(option)]
+    /// file name of the upload read from stdin, its extension decides how catbox serves it
+    /// If not provided detected images are named `stdin.png` etc., anything else `stdin`
+    pub name: Option<String>,
     #[argh(positional)]
-    /// file paths
+    /// file paths, `-` uploads stdin
     pub paths: Vec<PathBuf>,
 }
 
 // <--------------------------------->
 // Album Commands <------------------>
-#[derive(FromArgs, PartialEq, Eq, Debug, Clone)]
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
 /// Control your album
 #[argh(subcommand, name = "album")]
 pub struct AlbumCommand {
@@ -105,15 +257,19 @@ pub struct AlbumCommand {
     pub command: AlbumSubCommands,
 }
 
-#[derive(FromArgs, PartialEq, Eq, Debug, Clone)]
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
 #[argh(subcommand)]
 pub enum AlbumSubCommands {
     List(AlbumList),
     Add(AddFiles),
     Upload(UploadFiles),
+    Random(RandomFiles),
+    Sync(SyncAlbum),
+    Pull(PullAlbum),
+    Export(ExportAlbum),
 }
 
-#[derive(FromArgs, PartialEq, Eq, Debug, Clone)]
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
 /// Adding files via their short ids(allows url input) to the album.
 #[argh(subcommand, name = "add")]
 pub struct AddFiles {
@@ -125,19 +281,135 @@ pub struct AddFiles {
     pub files: Vec<String>,
 }
 
-#[derive(FromArgs, PartialEq, Eq, Debug, Clone)]
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
 /// Uploading files via their short ids(allows url input) to said album.
 #[argh(subcommand, name = "upload")]
 pub struct UploadFiles {
     /// the short of said album(the last part of the url)
     #[argh(option)]
     pub album: String,
+    #[argh(switch)]
+    /// strip EXIF/XMP/ICC metadata from JPEG, PNG and WebP files before uploading
+    pub strip_metadata: bool,
+    #[argh(option)]
+    /// print the uploaded files as links in this format, images and videos are embedded
+    /// Supported Values: [url, markdown, html, bbcode, template]
+    pub format: Option<LinkFormat>,
+    #[argh(option)]
+    /// print the uploaded files with this template, `{{name}}`, `{{url}}`, `{{size}}` and `{{short}}` are replaced
+    pub template: Option<String>,
     #[argh(positional)]
     /// files to add to album
     pub files: Vec<PathBuf>,
 }
 
-#[derive(FromArgs, PartialEq, Eq, Debug, Clone)]
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+/// Picks random files from an album.
+#[argh(subcommand, name = "random")]
+pub struct RandomFiles {
+    /// the short of said album(the last part of the url)
+    #[argh(option)]
+    pub album: String,
+    #[argh(option, default = "1")]
+    /// how many distinct files to pick
+    pub count: usize,
+    #[argh(option)]
+    /// only pick files with this extension, can be repeated
+    pub ext: Vec<String>,
+    #[argh(switch)]
+    /// download the picked files into the current directory
+    pub download: bool,
+    #[argh(switch)]
+    /// open the picked files with the default application
+    pub open: bool,
+    #[argh(option)]
+    /// seed of the picks, the same seed picks the same files from the same album
+    pub seed: Option<u64>,
+}
+
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+/// Mirrors a local directory into an album, uploading only new or changed files.
+/// Files are matched by the name they were uploaded under and compared by SHA-256.
+#[argh(subcommand, name = "sync")]
+pub struct SyncAlbum {
+    /// the short of said album(the last part of the url)
+    #[argh(option)]
+    pub album: String,
+    #[argh(switch)]
+    /// remove album files whose name no longer exists locally, the files stay on your account
+    pub delete: bool,
+    #[argh(switch)]
+    /// print the plan without uploading or removing anything
+    pub dry_run: bool,
+    #[argh(option)]
+    /// glob of paths to skip inside the directory, can be repeated
+    pub exclude: Vec<String>,
+    #[argh(positional)]
+    /// the directory to mirror
+    pub dir: PathBuf,
+}
+
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+/// Downloads the files of an album missing from a local directory.
+/// Pulled files are remembered in `.cbx-pull.json` inside the directory and never fetched again.
+#[argh(subcommand, name = "pull")]
+pub struct PullAlbum {
+    /// the short of said album(the last part of the url)
+    #[argh(option)]
+    pub album: String,
+    #[argh(switch)]
+    /// remove pulled files that left the album, other files are never touched
+    pub delete: bool,
+    #[argh(option, from_str_fn(parse_interval))]
+    /// keep pulling every interval, e.g. `30s`, `5m` or `1h`
+    pub watch: Option<Duration>,
+    #[argh(positional)]
+    /// the directory to pull into, created when missing
+    pub dir: PathBuf,
+}
+
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    let error =
+        || format!("invalid interval `{value}` (expected a number followed by ms, s, m or h)");
+    let split = value
+        .find(|x: char| !x.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number = number.parse::<u64>().map_err(|_| error())?;
+
+    let interval = match unit {
+        "ms" => Duration::from_millis(number),
+        "" | "s" => Duration::from_secs(number),
+        "m" => Duration::from_secs(number.checked_mul(60).ok_or_else(error)?),
+        "h" => Duration::from_secs(number.checked_mul(60 * 60).ok_or_else(error)?),
+        _ => return Err(error()),
+    };
+    if interval.is_zero() {
+        return Err(error());
+    }
+    Ok(interval)
+}
+
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+/// Exports an album as a gallery page, playlist or table.
+/// Names and sizes are included for the files on your account.
+#[argh(subcommand, name = "export")]
+pub struct ExportAlbum {
+    /// the short of said album(the last part of the url)
+    #[argh(option)]
+    pub album: String,
+    #[argh(option, default = "ExportFormat::Html")]
+    /// what to export, `m3u` only lists the video and audio files
+    /// Supported Values: [html, m3u, csv, json]
+    ///
+    /// If not provided defaults to `html`
+    pub format: ExportFormat,
+    #[argh(option)]
+    /// file to write the export to, printed to stdout when not given
+    pub out: Option<PathBuf>,
+}
+
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
 /// List all the albums from the logined state.
 /// if the `album` option is given, it will list the files of the album instead
 #[argh(subcommand, name = "list")]
@@ -148,3 +420,171 @@ pub struct AlbumList {
 }
 
 // <--------------------------------->
+// Watch Command <------------------>
+
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+/// Uploads every file created or changed in a directory, until interrupted.
+/// Files already in the directory when the watch starts are left alone.
+#[argh(subcommand, name = "watch")]
+pub struct WatchCommand {
+    #[argh(positional)]
+    /// the directory to watch
+    pub dir: PathBuf,
+    #[argh(switch, short = 'r')]
+    /// also watch every subdirectory
+    pub recursive: bool,
+    #[argh(option)]
+    /// add the uploaded files to this album(the last part of the url)
+    pub album: Option<String>,
+    #[argh(switch)]
+    /// upload files using Litterbox temporary hosting instead of Catbox.
+    pub use_litterbox: bool,
+    #[argh(option)]
+    /// expiration time for Litterbox uploads
+    /// Supported Values: [1h, 12h, 24h, 72h]
+    ///
+    /// If not provided defaults to `1h`
+    pub expiry: Option<LitterExpiry>,
+    #[argh(option)]
+    /// glob of paths to upload, can be repeated, every file is uploaded when none is given
+    pub include: Vec<String>,
+    #[argh(option)]
+    /// glob of paths to skip, can be repeated
+    pub exclude: Vec<String>,
+    #[argh(
+        option,
+        from_str_fn(parse_interval),
+        default = "Duration::from_secs(2)"
+    )]
+    /// how long a file has to stay untouched before it is uploaded, e.g. `500ms`
+    /// If not provided defaults to `2s`
+    pub debounce: Duration,
+    #[argh(switch)]
+    /// strip EXIF/XMP/ICC metadata from JPEG, PNG and WebP files before uploading
+    pub strip_metadata: bool,
+}
+
+// <--------------------------------->
+// Integrations Commands <------------------>
+
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+/// Uploading from screenshot tools.
+#[argh(subcommand, name = "integrations")]
+pub struct IntegrationsCommand {
+    #[argh(subcommand)]
+    pub command: IntegrationsSubCommands,
+}
+
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+#[argh(subcommand)]
+pub enum IntegrationsSubCommands {
+    Sharex(ShareXUploader),
+    Hook(ShellHook),
+}
+
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+/// Prints a ShareX custom uploader(.sxcu) uploading to your account.
+/// The file contains your userhash, anyone with it can upload as you.
+#[argh(subcommand, name = "sharex")]
+pub struct ShareXUploader {
+    #[argh(switch)]
+    /// upload to Litterbox instead, no account is needed
+    pub use_litterbox: bool,
+    #[argh(option)]
+    /// expiration time for Litterbox uploads
+    /// Supported Values: [1h, 12h, 24h, 72h]
+    ///
+    /// If not provided defaults to `1h`
+    pub expiry: Option<LitterExpiry>,
+    #[argh(option)]
+    /// file to write the uploader to, e.g. `catbox.sxcu`, printed to stdout when not given
+    pub out: Option<PathBuf>,
+}
+
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+/// Prints a shell function taking a screenshot and uploading it with `cbx file upload -`.
+#[argh(subcommand, name = "hook")]
+pub struct ShellHook {
+    #[argh(positional)]
+    /// the screenshot tool
+    /// Supported Values: [flameshot, grim, spectacle]
+    pub tool: HookTool,
+    #[argh(switch)]
+    /// upload to Litterbox instead
+    pub use_litterbox: bool,
+    #[argh(option)]
+    /// expiration time for Litterbox uploads
+    /// Supported Values: [1h, 12h, 24h, 72h]
+    ///
+    /// If not provided defaults to `1h`
+    pub expiry: Option<LitterExpiry>,
+}
+
+// <--------------------------------->
+// Serve Commands <------------------>
+
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+/// Runs a catbox compatible `api.php` that uploads through your account.
+/// Other services can upload without knowing your password or userhash.
+#[argh(subcommand, name = "serve")]
+pub struct ServeCommand {
+    #[argh(option, default = "SocketAddr::from((Ipv4Addr::LOCALHOST, 8080))")]
+    /// address to listen on
+    ///
+    /// If not provided defaults to `127.0.0.1:8080`
+    pub bind: SocketAddr,
+    #[argh(option)]
+    /// token a client has to send as its userhash, can be repeated
+    /// Every request is accepted when no token is given, which is only allowed on a loopback
+    /// address
+    pub token: Vec<String>,
+    #[argh(option)]
+    /// file with one token per line, `#` starts a comment
+    pub token_file: Option<PathBuf>,
+    #[argh(switch)]
+    /// forward `deletefiles`, so clients can delete any file of your account
+    pub allow_delete: bool,
+}
+
+// <--------------------------------->
+// Tui Commands <------------------>
+
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+/// Browses your albums and files in the terminal.
+/// Press `q` to quit, the available keys are shown at the bottom.
+#[argh(subcommand, name = "tui")]
+pub struct TuiCommand {
+    #[argh(switch)]
+    /// strip EXIF/XMP/ICC metadata from JPEG, PNG and WebP files before uploading
+    pub strip_metadata: bool,
+}
+
+// <--------------------------------->
+// Completions Commands <------------------>
+
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+/// Prints a script completing commands, options, album shorts and file slugs in your shell.
+/// Album shorts and file slugs come from the listings cached by `album list` and `file list`.
+#[argh(subcommand, name = "completions")]
+pub struct CompletionsCommand {
+    #[argh(positional)]
+    /// the shell to complete in
+    /// Supported Values: [bash, zsh, fish, nushell]
+    pub shell: Shell,
+    #[argh(switch, hidden_help)]
+    /// print the candidates for `words` instead, this is what the scripts call
+    pub complete: bool,
+    #[argh(positional, hidden_help)]
+    /// the command line after `cbx`, up to the word under the cursor
+    pub words: Vec<String>,
+}
+
+// <--------------------------------->
+// Man Commands <------------------>
+
+#[derive(FromArgs, ArgsInfo, PartialEq, Eq, Debug, Clone)]
+/// Prints the man page of cbx, e.g. `cbx man > ~/.local/share/man/man1/cbx.1`.
+#[argh(subcommand, name = "man")]
+pub struct ManCommand {}
+
+// <--------------------------------->
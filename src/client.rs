@@ -0,0 +1,636 @@
+use std::{
+    ops::RangeInclusive,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use bytes::Bytes;
+use futures_util::{stream, Stream, TryStreamExt};
+use reqwest::{cookie::Jar, Client, Url};
+use sha2::{Digest, Sha256};
+use tokio::{fs::File, io::AsyncWriteExt, sync::OnceCell};
+
+use crate::{
+    album::{Album, Files},
+    archive::Excludes,
+    cache::{Cached, Listing, ListingCache, Owner},
+    credentials::Credentials,
+    endpoints::Endpoints,
+    ensure,
+    errors::*,
+    file::UploadedFile,
+    history::HistoryEntry,
+    network::{create_spoof_client, NetworkOptions},
+    progress::{NoopReporter, ProgressReporter, Task, TaskKind},
+    sync::{self, PullReport, SyncPlan},
+    upload::{
+        to_hex, upload_temp_data, upload_temp_directory, upload_temp_file, LitterExpiry,
+        UploadOptions, Uploaded,
+    },
+    user::User,
+};
+
+/// Everything an operation needs to talk to catbox.
+///
+/// Cheap to clone, clones share the connection pool, cookie jar and progress reporter.
+#[derive(Clone)]
+pub struct Context {
+    http: Client,
+    endpoints: Arc<Endpoints>,
+    progress: Arc<dyn ProgressReporter>,
+}
+
+impl Context {
+    pub fn http(&self) -> &Client {
+        &self.http
+    }
+
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.endpoints
+    }
+
+    /// Where the progress of every operation is reported to.
+    pub fn progress(&self) -> &Arc<dyn ProgressReporter> {
+        &self.progress
+    }
+
+    /// The same context, reporting progress to `progress` instead.
+    #[must_use]
+    pub fn with_progress(&self, progress: Arc<dyn ProgressReporter>) -> Self {
+        Self {
+            progress,
+            ..self.clone()
+        }
+    }
+}
+
+/// Builder for `CatboxClient`.
+///
+/// # Example
+///
+/// ```
+/// let client = CatboxClient::builder()
+///     .credentials("kyle", "some_password")
+///     .build()?;
+/// ```
+#[derive(Default)]
+pub struct CatboxClientBuilder {
+    network: NetworkOptions,
+    endpoints: Endpoints,
+    credentials: Credentials,
+    progress: Option<Arc<dyn ProgressReporter>>,
+    cache: Option<ListingCache>,
+}
+
+impl CatboxClientBuilder {
+    /// Proxy and TLS settings of the underlying HTTP client.
+    #[must_use]
+    pub fn network(mut self, network: NetworkOptions) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Hosts to talk to, defaults to catbox.moe.
+    #[must_use]
+    pub fn endpoints(mut self, endpoints: Endpoints) -> Self {
+        self.endpoints = endpoints;
+        self
+    }
+
+    /// Logs in with the given account when an account is required.
+    #[must_use]
+    pub fn credentials(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.credentials = Credentials::Explicit {
+            username: username.into(),
+            password: password.into(),
+        };
+        self
+    }
+
+    /// Logs in with the account stored by `cbx config save`, see `credentials::load`.
+    #[must_use]
+    pub fn keyring_credentials(mut self) -> Self {
+        self.credentials = Credentials::Keyring;
+        self
+    }
+
+    /// Reports the progress of every operation to `progress`, by default nothing is reported.
+    #[must_use]
+    pub fn progress(mut self, progress: Arc<dyn ProgressReporter>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Remembers fetched albums and files in `cache`, see `CatboxClient::cached_albums`.
+    #[must_use]
+    pub fn listing_cache(mut self, cache: ListingCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn build(self) -> Result<CatboxClient, NetworkError> {
+        let http = create_spoof_client(&self.network, Arc::new(Jar::default()))?;
+
+        let progress = self.progress.unwrap_or_else(|| Arc::new(NoopReporter));
+
+        Ok(CatboxClient {
+            context: Context {
+                http,
+                endpoints: Arc::new(self.endpoints),
+                progress,
+            },
+            credentials: Arc::new(self.credentials),
+            user: Arc::new(OnceCell::new()),
+            owner: Arc::new(OnceLock::new()),
+            cache: self.cache,
+        })
+    }
+}
+
+/// Entry point of the library.
+///
+/// Holds one HTTP client for every request, and logs in lazily the first time an operation
+/// requires an account. Cheap to clone, clones share the login.
+#[derive(Clone)]
+pub struct CatboxClient {
+    context: Context,
+    credentials: Arc<Credentials>,
+    user: Arc<OnceCell<User>>,
+    /// resolved on first use, credentials may prompt or hit the keyring
+    owner: Arc<OnceLock<Option<Owner>>>,
+    cache: Option<ListingCache>,
+}
+
+impl CatboxClient {
+    pub fn builder() -> CatboxClientBuilder {
+        CatboxClientBuilder::default()
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    pub fn endpoints(&self) -> &Endpoints {
+        self.context.endpoints()
+    }
+
+    /// A client sharing the login of this one, reporting progress to `progress` instead.
+    ///
+    /// Lets a single operation be observed separately from the others.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let (reporter, mut events) = ChannelReporter::new();
+    /// client.with_progress(Arc::new(reporter)).upload("./happy.mp4", &options).await?;
+    /// ```
+    #[must_use]
+    pub fn with_progress(&self, progress: Arc<dyn ProgressReporter>) -> Self {
+        Self {
+            context: self.context.with_progress(progress),
+            ..self.clone()
+        }
+    }
+
+    /// The logged in `User`, logging in on first use.
+    pub async fn user(&self) -> Result<&User, UserError> {
+        self.user
+            .get_or_try_init(|| async {
+                let (username, password) = self.credentials.resolve()?;
+                User::new(self.context.clone(), username, password).await
+            })
+            .await
+    }
+
+    /// The logged in `User`, reporting to the progress reporter of this client.
+    async fn session(&self) -> Result<User, UserError> {
+        Ok(self
+            .user()
+            .await?
+            .with_progress(self.context.progress().clone()))
+    }
+
+    /// Uploads a file(or a directory as an archive) to the account.
+    pub async fn upload(
+        &self,
+        path: impl AsRef<Path> + Send,
+        options: &UploadOptions,
+    ) -> Result<Uploaded, UserError> {
+        let path = path.as_ref();
+        let user = self.session().await?;
+        let uploaded = if path.is_dir() {
+            user.upload_directory(path, options).await
+        } else {
+            user.upload_file(path, options).await
+        }?;
+        let name = path
+            .is_file()
+            .then(|| path.file_name())
+            .flatten()
+            .map(|x| x.to_string_lossy().into_owned());
+        self.remember_upload(&uploaded.url, name, Some(uploaded.size));
+        Ok(uploaded)
+    }
+
+    /// Uploads a file(or a directory as an archive) to Litterbox, no account is required.
+    pub async fn upload_temp(
+        &self,
+        path: impl AsRef<Path> + Send,
+        options: &UploadOptions,
+        expiry: LitterExpiry,
+    ) -> Result<Uploaded, UploadFileError> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            upload_temp_directory(path, options, expiry, &self.context).await
+        } else {
+            upload_temp_file(path, options, expiry, &self.context).await
+        }
+    }
+
+    /// Uploads in-memory data, e.g. read from stdin, as a file named `file_name`.
+    pub async fn upload_data(
+        &self,
+        file_name: String,
+        bytes: Bytes,
+        options: &UploadOptions,
+    ) -> Result<Uploaded, UserError> {
+        let uploaded = self
+            .session()
+            .await?
+            .upload_data(file_name.clone(), bytes, options)
+            .await?;
+        self.remember_upload(&uploaded.url, Some(file_name), Some(uploaded.size));
+        Ok(uploaded)
+    }
+
+    /// Uploads in-memory data to Litterbox, no account is required.
+    pub async fn upload_temp_data(
+        &self,
+        file_name: String,
+        bytes: Bytes,
+        options: &UploadOptions,
+        expiry: LitterExpiry,
+    ) -> Result<Uploaded, UploadFileError> {
+        upload_temp_data(file_name, bytes, options, expiry, &self.context).await
+    }
+
+    /// Uploads a stream of unknown length to the account as a file named `file_name`.
+    pub async fn upload_stream<S>(
+        &self,
+        file_name: String,
+        stream: S,
+    ) -> Result<Uploaded, UserError>
+    where
+        S: Stream<Item = Result<Bytes, std::io::Error>> + Send + 'static,
+    {
+        let uploaded = self
+            .session()
+            .await?
+            .upload_stream(file_name.clone(), stream)
+            .await?;
+        self.remember_upload(&uploaded.url, Some(file_name), Some(uploaded.size));
+        Ok(uploaded)
+    }
+
+    /// Lets catbox download `url` into the account, returning the url of the new file.
+    pub async fn upload_url(&self, url: &Url) -> Result<String, UserError> {
+        let uploaded = self.session().await?.upload_url(url).await?;
+        self.remember_upload(&uploaded, None, None);
+        Ok(uploaded)
+    }
+
+    /// The files of the account, from the listing cache while it is fresh.
+    pub async fn list_files(&self) -> Result<Vec<UploadedFile>, UserError> {
+        match self.fresh_files() {
+            Some(files) => Ok(files),
+            None => self.refresh_files().await,
+        }
+    }
+
+    /// Fetches the files of the account, ignoring and updating the listing cache.
+    pub async fn refresh_files(&self) -> Result<Vec<UploadedFile>, UserError> {
+        let files = self.session().await?.fetch_uploaded_files().await?;
+        self.remember(|x| x.files = Some(Cached::now(files.clone())));
+        Ok(files)
+    }
+
+    /// Streams the files on pages `pages` of the account, see `User::uploaded_files`.
+    ///
+    /// Always fetched, as single pages aren't cached.
+    pub fn files(
+        &self,
+        pages: RangeInclusive<u32>,
+    ) -> impl Stream<Item = Result<UploadedFile, UserError>> + Send + '_ {
+        stream::once(self.session())
+            .map_ok(move |user| user.uploaded_files(pages.clone()))
+            .try_flatten()
+    }
+
+    /// The albums of the account, from the listing cache while it is fresh.
+    pub async fn list_albums(&self) -> Result<Vec<Album>, UserError> {
+        match self.fresh_albums() {
+            Some(albums) => Ok(albums),
+            None => self.refresh_albums().await,
+        }
+    }
+
+    /// Fetches the albums of the account, ignoring and updating the listing cache.
+    pub async fn refresh_albums(&self) -> Result<Vec<Album>, UserError> {
+        let albums = self.session().await?.fetch_albums().await?;
+        self.remember(|x| x.albums = Some(Cached::now(albums.clone())));
+        Ok(albums)
+    }
+
+    /// The albums of the last `list_albums`, however old, without logging in.
+    ///
+    /// `None` without a listing cache, or when the albums were never listed.
+    pub fn cached_albums(&self) -> Option<Cached<Vec<Album>>> {
+        let (cache, owner) = self.cache.as_ref().zip(self.owner())?;
+        cache.load(owner).albums
+    }
+
+    /// The files of the last `list_files`, however old, without logging in, see `cached_albums`.
+    pub fn cached_files(&self) -> Option<Cached<Vec<UploadedFile>>> {
+        let (cache, owner) = self.cache.as_ref().zip(self.owner())?;
+        cache.load(owner).files
+    }
+
+    /// The cached albums, if they were listed within the ttl of the listing cache.
+    pub fn fresh_albums(&self) -> Option<Vec<Album>> {
+        let ttl = self.cache.as_ref()?.ttl();
+        self.cached_albums()
+            .filter(|x| x.is_fresh(ttl))
+            .map(|x| x.value)
+    }
+
+    /// The cached files, if they were listed within the ttl of the listing cache.
+    pub fn fresh_files(&self) -> Option<Vec<UploadedFile>> {
+        let ttl = self.cache.as_ref()?.ttl();
+        self.cached_files()
+            .filter(|x| x.is_fresh(ttl))
+            .map(|x| x.value)
+    }
+
+    /// Drops the cached listings, for changes made to the account behind the back of this client.
+    pub fn invalidate_listings(&self) {
+        self.remember(|x| {
+            x.albums = None;
+            x.files = None;
+        });
+    }
+
+    /// The account the listings of this client belong to, `None` without credentials.
+    fn owner(&self) -> Option<&Owner> {
+        self.owner
+            .get_or_init(|| {
+                let (username, _) = self.credentials.resolve().ok()?;
+                Some(Owner {
+                    account: self.endpoints().account(),
+                    username,
+                })
+            })
+            .as_ref()
+    }
+
+    /// Updates the listing cache, a cache that can't be written is left as it is.
+    fn remember(&self, update: impl FnOnce(&mut Listing)) {
+        if let Some((cache, owner)) = self.cache.as_ref().zip(self.owner()) {
+            let _ = cache.update(owner, update);
+        }
+    }
+
+    /// Adds a file just uploaded to the cached files, so they needn't be fetched again to know of
+    /// it. Files that were never listed, or a url that can't be parsed, leave them as they are.
+    fn remember_upload(&self, url: &str, name: Option<String>, size: Option<u64>) {
+        let Ok(url) = Url::parse(url) else {
+            return;
+        };
+        let mut file = UploadedFile::new(url);
+        file.name = name;
+        file.size = size;
+        self.remember(|x| {
+            if let Some(files) = &mut x.files {
+                files.value.push(file);
+            }
+        });
+    }
+
+    /// Resolves an album from its short or its url, with or without the scheme.
+    pub fn album(&self, short_or_url: &str) -> Result<Album, AlbumError> {
+        let url = self.endpoints().album_url(short_or_url).map_err(|source| {
+            AlbumError::InvalidAlbumUrl {
+                source,
+                url: short_or_url.to_owned(),
+            }
+        })?;
+        Ok(Album::new(url))
+    }
+
+    /// Resolves a file slug(`short.ext`) from either the slug itself or the file url.
+    pub fn file_slug(&self, slug_or_url: &str) -> String {
+        Url::parse(slug_or_url)
+            .ok()
+            .and_then(|url| self.endpoints().file_slug(&url).map(ToOwned::to_owned))
+            .unwrap_or_else(|| slug_or_url.to_owned())
+    }
+
+    pub async fn album_files(&self, album: &Album) -> Result<Files, AlbumError> {
+        album.fetch_files(&self.context).await
+    }
+
+    /// The album with its public page's title, description and file count, plus its files.
+    pub async fn fetch_album(&self, album: &Album) -> Result<(Album, Files), AlbumError> {
+        album.fetch(&self.context).await
+    }
+
+    pub async fn create_album(
+        &self,
+        title: &str,
+        description: &str,
+        slugs: &[&str],
+    ) -> Result<Album, UserError> {
+        let album = self
+            .session()
+            .await?
+            .create_album(title, description, slugs)
+            .await?;
+        self.remember(|x| x.albums = None);
+        Ok(album)
+    }
+
+    /// Adds a file of the account to `album`.
+    ///
+    /// The slug is checked against the cached files first, they are only fetched when it isn't
+    /// among them.
+    pub async fn add_to_album(&self, album: &Album, slug: &str) -> Result<(), UserError> {
+        let task = Task::start(
+            self.context.progress(),
+            TaskKind::Other,
+            format!("Uploading '{slug}' to album"),
+            None,
+        );
+
+        task.complete(
+            async {
+                let owns = |files: &[UploadedFile]| {
+                    files
+                        .iter()
+                        .any(|x| self.endpoints().file_slug(&x.url) == Some(slug))
+                };
+                let owned = match self.fresh_files() {
+                    Some(files) if owns(&files) => true,
+                    _ => owns(&self.refresh_files().await?),
+                };
+                ensure!(
+                    owned,
+                    UserError::InvalidSlug {
+                        slug: slug.to_owned()
+                    }
+                );
+
+                self.session().await?.add_to_album(album, &[slug]).await?;
+                self.remember(|x| x.albums = None);
+                Ok(())
+            }
+            .await,
+        )
+    }
+
+    pub async fn remove_from_album(&self, album: &Album, slugs: &[&str]) -> Result<(), UserError> {
+        self.session()
+            .await?
+            .remove_from_album(album, slugs)
+            .await?;
+        self.remember(|x| x.albums = None);
+        Ok(())
+    }
+
+    pub async fn delete_album(&self, album: &Album) -> Result<(), UserError> {
+        self.session().await?.delete_album(album).await?;
+        self.remember(|x| {
+            if let Some(albums) = &mut x.albums {
+                albums.value.retain(|x| x.url != album.url);
+            }
+        });
+        Ok(())
+    }
+
+    pub async fn delete_files(&self, slugs: &[&str]) -> Result<(), UserError> {
+        self.session().await?.delete_files(slugs).await?;
+        self.remember(|x| {
+            if let Some(files) = &mut x.files {
+                files.value.retain(|x| !slugs.contains(&x.slug().as_str()));
+            }
+            // the files are gone from every album too
+            x.albums = None;
+        });
+        Ok(())
+    }
+
+    /// Mirrors `dir` into `album`, see `sync::plan`.
+    pub async fn plan_sync(
+        &self,
+        album: &Album,
+        dir: &Path,
+        excludes: &Excludes,
+        delete: bool,
+        history: &[HistoryEntry],
+    ) -> Result<SyncPlan, UserError> {
+        sync::plan(self, album, dir, excludes, delete, history).await
+    }
+
+    /// Downloads the files of `album` missing from `dir`, see `sync::pull`.
+    pub async fn pull_album(
+        &self,
+        album: &Album,
+        dir: &Path,
+        delete: bool,
+    ) -> Result<PullReport, DownloadError> {
+        sync::pull(self, album, dir, delete).await
+    }
+
+    /// Hex encoded SHA-256 of the file at `url`, hashed while streaming without touching the disk.
+    pub async fn sha256(&self, url: Url) -> Result<String, NetworkError> {
+        let mut stream = self
+            .context
+            .http()
+            .get(url)
+            .send()
+            .await
+            .map_err(NetworkError::DownloadRequest)?
+            .error_for_status()
+            .map_err(NetworkError::ErrorCode)?
+            .bytes_stream();
+
+        let mut hasher = Sha256::new();
+        while let Some(chunk) = stream
+            .try_next()
+            .await
+            .map_err(NetworkError::DownloadRequest)?
+        {
+            hasher.update(&chunk);
+        }
+
+        Ok(to_hex(&hasher.finalize()))
+    }
+
+    /// Downloads `url` into `path`, returning the amount of bytes written.
+    pub async fn download(&self, url: Url, path: impl AsRef<Path>) -> Result<u64, DownloadError> {
+        let path = path.as_ref();
+        let write_error = |source| DownloadError::WriteFile {
+            source,
+            file: path.to_path_buf(),
+        };
+
+        let response = self
+            .context
+            .http()
+            .get(url)
+            .send()
+            .await
+            .map_err(NetworkError::DownloadRequest)?
+            .error_for_status()
+            .map_err(NetworkError::ErrorCode)?;
+
+        let task = Task::start(
+            self.context.progress(),
+            TaskKind::Download,
+            path.display(),
+            response.content_length(),
+        );
+        let mut stream = response.bytes_stream();
+
+        // written next to `path` first, so a failed download never leaves half a file in place
+        let partial = path.with_file_name(format!(
+            ".{}.part",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        let written = async {
+            let mut file = File::create(&partial).await.map_err(write_error)?;
+            let mut written = 0;
+
+            while let Some(chunk) = stream
+                .try_next()
+                .await
+                .map_err(NetworkError::DownloadRequest)?
+            {
+                file.write_all(&chunk).await.map_err(write_error)?;
+                written += chunk.len() as u64;
+                task.advance(chunk.len() as u64);
+            }
+            file.flush().await.map_err(write_error)?;
+            drop(file);
+
+            tokio::fs::rename(&partial, path)
+                .await
+                .map_err(write_error)?;
+            Ok(written)
+        }
+        .await;
+
+        if written.is_err() {
+            let _ = tokio::fs::remove_file(&partial).await;
+        }
+        task.clone().complete(written)
+    }
+}
@@ -0,0 +1,347 @@
+use std::str::FromStr;
+
+use argh::{ArgsInfo, CommandInfoWithArgs, FlagInfo, FlagInfoKind, Optionality};
+use catbox::cache::ListingCache;
+use serde_json::json;
+
+use crate::cli::Cli;
+
+/// Positionals completed with the file slugs of the account, by command and positional name.
+const SLUG_POSITIONALS: [(&str, &str); 1] = [("album add", "files")];
+
+/// Options completed with the album shorts of the account.
+const ALBUM_OPTIONS: [&str; 1] = ["--album"];
+
+/// Shells `cbx completions` has a script for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Nushell,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            "nushell" => Ok(Self::Nushell),
+            s => Err(format!(
+                "invalid shell `{s}` (expected one of: bash, zsh, fish, nushell)"
+            )),
+        }
+    }
+}
+
+impl Shell {
+    /// Script registering the completions of `cbx`.
+    ///
+    /// The script only forwards the command line to `cbx completions <shell> --complete`, so the
+    /// completions always match the installed `cbx`.
+    pub const fn script(self) -> &'static str {
+        match self {
+            Self::Bash => BASH,
+            Self::Zsh => ZSH,
+            Self::Fish => FISH,
+            Self::Nushell => NUSHELL,
+        }
+    }
+
+    /// `candidates` in the format the script of this shell reads.
+    pub fn render(self, candidates: &[Candidate]) -> String {
+        match self {
+            Self::Bash => candidates
+                .iter()
+                .map(|x| format!("{}\n", x.value))
+                .collect(),
+            Self::Zsh => candidates
+                .iter()
+                .map(|x| match x.description.as_str() {
+                    "" => format!("{}\n", x.value.replace(':', "\\:")),
+                    description => format!("{}:{description}\n", x.value.replace(':', "\\:")),
+                })
+                .collect(),
+            Self::Fish => candidates
+                .iter()
+                .map(|x| format!("{}\t{}\n", x.value, x.description))
+                .collect(),
+            Self::Nushell => {
+                let candidates = candidates
+                    .iter()
+                    .map(|x| json!({ "value": x.value, "description": x.description }))
+                    .collect::<Vec<_>>();
+                format!("{}\n", serde_json::Value::Array(candidates))
+            }
+        }
+    }
+}
+
+/// A word the word under the cursor can be completed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub value: String,
+    pub description: String,
+}
+
+impl Candidate {
+    pub fn new(value: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            value: value.into(),
+            description: description.into(),
+        }
+    }
+}
+
+/// What the word under the cursor is completed with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Completion {
+    Candidates(Vec<Candidate>),
+    /// the shorts of the albums of the account
+    Albums,
+    /// the slugs of the files of the account
+    Files,
+    /// nothing `cbx` knows of, the shell completes paths instead
+    Paths,
+}
+
+/// What to complete the last of `words` with.
+///
+/// `words` is the command line after `cbx`, up to the word under the cursor, which may be empty.
+/// Candidates aren't filtered by the word under the cursor yet.
+pub fn complete(words: &[String]) -> Completion {
+    let Some((current, done)) = words.split_last() else {
+        return complete(&[String::new()]);
+    };
+
+    let root = Cli::get_args_info();
+    let mut command = &root;
+    let mut path = Vec::new();
+    let mut used = Vec::new();
+    let mut positionals = 0;
+    let mut pending = None;
+    let mut only_positionals = false;
+
+    for word in done {
+        if pending.take().is_some() {
+            continue;
+        }
+        if !only_positionals && word == "--" {
+            only_positionals = true;
+        } else if !only_positionals && word.starts_with('-') && word.len() > 1 {
+            let name = word.split_once('=').map_or(word.as_str(), |(x, _)| x);
+            if let Some(flag) = find_flag(command, name) {
+                used.push(flag.long);
+                let takes_value = matches!(flag.kind, FlagInfoKind::Option { .. });
+                if takes_value && !word.contains('=') {
+                    pending = Some(flag);
+                }
+            }
+        } else if let Some(sub) = command.commands.iter().find(|x| x.name == word) {
+            command = &sub.command;
+            path.push(sub.name);
+            used.clear();
+            positionals = 0;
+        } else {
+            positionals += 1;
+        }
+    }
+
+    if let Some(flag) = pending {
+        return option_values(flag);
+    }
+    if current.contains('=') {
+        return Completion::Paths;
+    }
+    if !only_positionals && current.starts_with('-') {
+        return Completion::Candidates(flags(command, &used));
+    }
+    if !command.commands.is_empty() {
+        return Completion::Candidates(
+            command
+                .commands
+                .iter()
+                .map(|x| Candidate::new(x.name, summary(x.command.description)))
+                .collect(),
+        );
+    }
+
+    let repeats = |x: &argh::PositionalInfo| {
+        matches!(x.optionality, Optionality::Repeating | Optionality::Greedy)
+    };
+    let positional = command
+        .positionals
+        .get(positionals)
+        .or_else(|| command.positionals.last().filter(|x| repeats(x)));
+    match positional {
+        Some(positional) => {
+            let path = path.join(" ");
+            if SLUG_POSITIONALS.contains(&(path.as_str(), positional.name)) {
+                return Completion::Files;
+            }
+            match supported_values(positional.description) {
+                Some(values) => Completion::Candidates(values),
+                None => Completion::Paths,
+            }
+        }
+        None => Completion::Candidates(flags(command, &used)),
+    }
+}
+
+/// The candidates of `completion`, from the listing cache however old it is.
+///
+/// Nothing is fetched, so completing never waits on catbox. Listings that were never cached
+/// complete to nothing.
+pub fn candidates(cache: &ListingCache, completion: Completion) -> Vec<Candidate> {
+    match completion {
+        Completion::Candidates(x) => x,
+        Completion::Albums => cache
+            .load_any()
+            .albums
+            .map(|x| x.value)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|x| {
+                let short = x.url.path_segments()?.rfind(|x| !x.is_empty())?.to_owned();
+                Some(Candidate::new(short, x.title.unwrap_or_default()))
+            })
+            .collect(),
+        Completion::Files => cache
+            .load_any()
+            .files
+            .map(|x| x.value)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|x| Candidate::new(x.slug(), x.name.unwrap_or_default()))
+            .collect(),
+        Completion::Paths => Vec::new(),
+    }
+}
+
+fn find_flag<'a>(command: &'a CommandInfoWithArgs, name: &str) -> Option<&'a FlagInfo<'a>> {
+    command.flags.iter().find(|x| {
+        x.long == name
+            || x.short.is_some() && name.strip_prefix('-').and_then(|x| x.parse().ok()) == x.short
+    })
+}
+
+fn option_values(flag: &FlagInfo<'_>) -> Completion {
+    if ALBUM_OPTIONS.contains(&flag.long) {
+        return Completion::Albums;
+    }
+    supported_values(flag.description).map_or(Completion::Paths, Completion::Candidates)
+}
+
+/// The flags of `command`, leaving out those in `used` which can't be repeated.
+fn flags(command: &CommandInfoWithArgs, used: &[&str]) -> Vec<Candidate> {
+    command
+        .flags
+        .iter()
+        .filter(|x| !x.hidden)
+        .filter(|x| x.optionality == Optionality::Repeating || !used.contains(&x.long))
+        .map(|x| Candidate::new(x.long, summary(x.description)))
+        .collect()
+}
+
+/// The values listed by the `Supported Values: [...]` line of a description.
+fn supported_values(description: &str) -> Option<Vec<Candidate>> {
+    let (_, rest) = description.split_once("Supported Values: [")?;
+    let (values, _) = rest.split_once(']')?;
+    Some(
+        values
+            .split(',')
+            .map(|x| Candidate::new(x.trim(), ""))
+            .collect(),
+    )
+}
+
+/// A description as a single line, with the braces argh needs escaped unescaped.
+pub fn clean(description: &str) -> String {
+    description
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("{{", "{")
+        .replace("}}", "}")
+}
+
+/// The first sentence of a description, short enough to be shown next to a candidate.
+fn summary(description: &str) -> String {
+    let description = clean(description);
+    // a sentence ends where the next one starts with a capital, `e.g. ` doesn't
+    let sentence = description
+        .match_indices(". ")
+        .find(|(i, _)| description[i + 2..].starts_with(char::is_uppercase))
+        .map(|(i, _)| i);
+    let end = ["Supported Values:", "If not provided"]
+        .iter()
+        .filter_map(|x| description.find(x))
+        .chain(sentence)
+        .min()
+        .unwrap_or(description.len());
+    description[..end].trim().trim_end_matches('.').to_owned()
+}
+
+const BASH: &str = r#"# cbx completions for bash, add `source <(cbx completions bash)` to ~/.bashrc
+_cbx() {
+    local line="${COMP_LINE:0:COMP_POINT}"
+    local -a words
+    read -ra words <<< "$line"
+    [[ "$line" == *[[:space:]] ]] && words+=("")
+    local IFS=$'\n'
+    COMPREPLY=($(cbx completions bash --complete -- "${words[@]:1}" 2>/dev/null))
+}
+complete -o default -F _cbx cbx
+"#;
+
+const ZSH: &str = r#"#compdef cbx
+# cbx completions for zsh, save as `_cbx` in a directory of your $fpath
+# or add `source <(cbx completions zsh)` to ~/.zshrc after compinit
+_cbx() {
+    local -a candidates
+    candidates=("${(@f)$(cbx completions zsh --complete -- "${(@)words[2,CURRENT]}" 2>/dev/null)}")
+    if [[ -n "${candidates[1]}" ]]; then
+        _describe 'cbx' candidates
+    else
+        _files
+    fi
+}
+if [[ "${funcstack[1]}" == "_cbx" ]]; then
+    _cbx "$@"
+else
+    compdef _cbx cbx
+fi
+"#;
+
+const FISH: &str = r#"# cbx completions for fish, save as ~/.config/fish/completions/cbx.fish
+function __cbx_complete
+    set -l words (commandline -opc)
+    set -e words[1]
+    set -l candidates (cbx completions fish --complete -- $words (commandline -ct) 2>/dev/null)
+    if test (count $candidates) -eq 0
+        __fish_complete_path (commandline -ct)
+    else
+        printf '%s\n' $candidates
+    end
+end
+complete -c cbx -f -a '(__cbx_complete)'
+"#;
+
+const NUSHELL: &str = r#"# cbx completions for nushell, save the output and `source` it from your config.nu
+# Other commands are still completed by the external completer you had before.
+let cbx_fallback = $env.config.completions.external.completer?
+$env.config.completions.external.enable = true
+$env.config.completions.external.completer = {|spans|
+    if $spans.0 == "cbx" {
+        let candidates = ^cbx completions nushell --complete -- ...($spans | skip 1) | from json
+        if ($candidates | is-empty) { null } else { $candidates }
+    } else if $cbx_fallback != null {
+        do $cbx_fallback $spans
+    } else {
+        null
+    }
+}
+"#;
@@ -0,0 +1,173 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    errors::*,
+    network::{parse_proxy, read_ca_bundle, TlsBackend},
+};
+
+/// Persistent settings stored next to the keyring credentials.
+///
+/// Lives in `<config dir>/catbox-cli/config.json`, unless overridden by `CBX_CONFIG`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// strip EXIF/XMP/ICC metadata from images before every upload
+    pub strip_metadata: bool,
+    /// proxy used for every request, `none` disables the proxy environment variables
+    pub proxy: Option<String>,
+    /// PEM file with extra root certificates to trust
+    pub ca_bundle: Option<PathBuf>,
+    pub tls: TlsBackend,
+    /// main site of a self-hosted catbox clone or mock server
+    pub base_url: Option<Url>,
+    /// where uploaded files are served from, defaults to `base-url`
+    pub files_url: Option<Url>,
+    /// Litterbox site, defaults to `base-url`
+    pub litterbox_url: Option<Url>,
+    /// seconds album and file listings are cached for, defaults to `cache::DEFAULT_TTL`
+    pub listing_ttl: Option<u64>,
+}
+
+impl Config {
+    pub fn path() -> Result<PathBuf, ConfigError> {
+        if let Some(path) = std::env::var_os("CBX_CONFIG") {
+            return Ok(PathBuf::from(path));
+        }
+        Ok(dirs::config_dir()
+            .ok_or(ConfigError::LackOfConfigDir)?
+            .join("catbox-cli")
+            .join("config.json"))
+    }
+
+    /// Loads the config file, falling back to the defaults when it doesn't exist yet.
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = Self::path()?;
+
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(source) => return Err(ConfigError::ReadConfig { source, path }),
+        };
+
+        serde_json::from_str(&text).map_err(|source| ConfigError::ParseConfig { source, path })
+    }
+
+    /// Loads the config file like `load`, except that settings which can't be parsed are reset
+    /// to their defaults, for the `config` commands to repair a broken config with.
+    ///
+    /// Returns the names of the settings that were reset, along with the error that made them.
+    pub fn load_salvaged() -> Result<(Self, Vec<String>, Option<ConfigError>), ConfigError> {
+        let error = match Self::load() {
+            Ok(config) => return Ok((config, Vec::new(), None)),
+            Err(error @ ConfigError::ParseConfig { .. }) => error,
+            Err(error) => return Err(error),
+        };
+
+        let known = match serde_json::to_value(Self::default()) {
+            Ok(serde_json::Value::Object(x)) => x,
+            _ => unreachable!("config serializes to an object"),
+        };
+        let text = std::fs::read_to_string(Self::path()?).unwrap_or_default();
+        let Ok(settings) = serde_json::from_str::<serde_json::Map<_, _>>(&text) else {
+            return Ok((
+                Self::default(),
+                known.keys().cloned().collect(),
+                Some(error),
+            ));
+        };
+
+        let mut kept = serde_json::Map::new();
+        let mut reset = Vec::new();
+        for (key, value) in settings {
+            let single = serde_json::Map::from_iter([(key.clone(), value.clone())]);
+            if known.contains_key(&key) && serde_json::from_value::<Self>(single.into()).is_ok() {
+                kept.insert(key, value);
+            } else {
+                reset.push(key);
+            }
+        }
+        let config = serde_json::from_value(kept.into()).unwrap_or_default();
+        Ok((config, reset, Some(error)))
+    }
+
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let path = Self::path()?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|source| ConfigError::WriteConfig {
+                source,
+                path: path.clone(),
+            })?;
+        }
+
+        let text =
+            serde_json::to_string_pretty(self).map_err(|source| ConfigError::SerializeConfig {
+                source,
+                path: path.clone(),
+            })?;
+
+        std::fs::write(&path, text).map_err(|source| ConfigError::WriteConfig { source, path })
+    }
+
+    /// Sets a single setting by its kebab-case name.
+    ///
+    /// An empty `value` unsets optional settings.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let invalid = || ConfigError::InvalidConfigValue {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        };
+        let optional = || Some(value.to_owned()).filter(|x| !x.is_empty());
+        // settings the http client is built from are checked now, a bad one would stop every run
+        let unusable = |source| ConfigError::UnusableConfigValue {
+            source,
+            key: key.to_owned(),
+            value: value.to_owned(),
+        };
+
+        match key {
+            "strip-metadata" => self.strip_metadata = value.parse().map_err(|_| invalid())?,
+            "proxy" => {
+                if let Some(proxy) = optional().filter(|x| x != "none") {
+                    parse_proxy(&proxy).map_err(unusable)?;
+                }
+                self.proxy = optional();
+            }
+            "ca-bundle" => {
+                let path = optional().map(PathBuf::from);
+                if let Some(path) = &path {
+                    read_ca_bundle(path).map_err(unusable)?;
+                }
+                self.ca_bundle = path;
+            }
+            "tls" => self.tls = value.parse().map_err(|_| invalid())?,
+            "listing-ttl" => {
+                self.listing_ttl = optional()
+                    .map(|x| x.parse())
+                    .transpose()
+                    .map_err(|_| invalid())?;
+            }
+            "base-url" | "files-url" | "litterbox-url" => {
+                // urls that can't have paths joined onto them, like `mailto:`, can't be sites
+                let url = optional()
+                    .map(|x| Url::parse(&x).ok().filter(|x| !x.cannot_be_a_base()))
+                    .map(|x| x.ok_or_else(invalid))
+                    .transpose()?;
+                match key {
+                    "base-url" => self.base_url = url,
+                    "files-url" => self.files_url = url,
+                    _ => self.litterbox_url = url,
+                }
+            }
+            _ => {
+                return Err(ConfigError::UnknownConfigKey {
+                    key: key.to_owned(),
+                })
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use keyring::Entry;
+
+use crate::errors::*;
+
+pub fn username_entry() -> Result<Entry, KeyringError> {
+    Entry::new("catbox-cli", "username").map_err(KeyringError::KeyringInitilization)
+}
+
+pub fn password_entry() -> Result<Entry, KeyringError> {
+    Entry::new("catbox-cli", "password").map_err(KeyringError::KeyringInitilization)
+}
+
+/// Loads the account saved by `cbx config save`.
+///
+/// `CBX_USERNAME` and `CBX_PASSWORD` take precedence, for CI and machines without a keyring.
+pub fn load() -> Result<(String, String), KeyringError> {
+    let username = match std::env::var("CBX_USERNAME") {
+        Ok(username) => username,
+        Err(_) => username_entry()?
+            .get_password()
+            .map_err(KeyringError::LackOfUser)?,
+    };
+    let password = match std::env::var("CBX_PASSWORD") {
+        Ok(password) => password,
+        Err(_) => password_entry()?
+            .get_password()
+            .map_err(KeyringError::LackOfPassword)?,
+    };
+    Ok((username, password))
+}
+
+/// Where a `CatboxClient` gets its account from.
+#[derive(Debug, Clone, Default)]
+pub(crate) enum Credentials {
+    #[default]
+    Missing,
+    Explicit {
+        username: String,
+        password: String,
+    },
+    Keyring,
+}
+
+impl Credentials {
+    pub(crate) fn resolve(&self) -> Result<(String, String), UserError> {
+        match self {
+            Self::Missing => Err(UserError::LackOfCredentials),
+            Self::Explicit { username, password } => Ok((username.clone(), password.clone())),
+            Self::Keyring => load().map_err(Into::into),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use url::Url;
+
+/// Hosts every request is sent to.
+///
+/// Defaults to catbox.moe, but can point to a self-hosted clone or a mock server.
+/// Every url has to be able to be a base(`Url::cannot_be_a_base` is false).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoints {
+    /// the main site, hosting the user pages, the api and albums
+    pub base: Url,
+    /// where uploaded files are served from
+    pub files: Url,
+    /// the Litterbox site
+    pub litterbox: Url,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            base: Url::parse("https://catbox.moe/").expect("valid url(compile time issue)"),
+            files: Url::parse("https://files.catbox.moe/").expect("valid url(compile time issue)"),
+            litterbox: Url::parse("https://litterbox.catbox.moe/")
+                .expect("valid url(compile time issue)"),
+        }
+    }
+}
+
+impl Endpoints {
+    /// Serves everything from a single host, which is how most clones and mock servers are set up.
+    pub fn from_base_url(base: Url) -> Self {
+        let base = with_trailing_slash(base);
+        Self {
+            files: base.clone(),
+            litterbox: base.clone(),
+            base,
+        }
+    }
+
+    pub fn with_files(mut self, files: Url) -> Self {
+        self.files = with_trailing_slash(files);
+        self
+    }
+
+    pub fn with_litterbox(mut self, litterbox: Url) -> Self {
+        self.litterbox = with_trailing_slash(litterbox);
+        self
+    }
+
+    pub fn api(&self) -> Url {
+        join(&self.base, "user/api.php")
+    }
+
+    pub fn login(&self) -> Url {
+        join(&self.base, "user/dologin.php")
+    }
+
+    pub fn account(&self) -> Url {
+        join(&self.base, "user/manage.php")
+    }
+
+    pub fn uploaded_files(&self) -> Url {
+        join(&self.base, "user/view.php")
+    }
+
+    /// Page `page` of `uploaded_files`, counting from 1.
+    pub fn uploaded_files_page(&self, page: u32) -> Url {
+        let mut url = self.uploaded_files();
+        url.query_pairs_mut().append_pair("page", &page.to_string());
+        url
+    }
+
+    pub fn albums(&self) -> Url {
+        join(&self.base, "user/manage_albums.php")
+    }
+
+    pub fn album(&self, short: &str) -> Url {
+        let mut url = join(&self.base, "c/");
+        url.path_segments_mut()
+            .expect("base url is validated when configured")
+            .pop_if_empty()
+            .push(short);
+        url
+    }
+
+    pub fn litter_api(&self) -> Url {
+        join(&self.litterbox, "resources/internals/api.php")
+    }
+
+    /// Extracts the short of an album url, `None` if `url` is not an album of the main site.
+    ///
+    /// A trailing `/` is ignored.
+    pub fn album_short<'a>(&self, url: &'a Url) -> Option<&'a str> {
+        if !same_host(url, &self.base) {
+            return None;
+        }
+        let short = url
+            .path()
+            .strip_prefix(self.base.path())?
+            .strip_prefix("c/")?;
+        let short = short.strip_suffix('/').unwrap_or(short);
+        Some(short).filter(|x| !x.is_empty() && !x.contains('/'))
+    }
+
+    /// The album url of a short, an album url, or one without its scheme(`catbox.moe/c/short`).
+    pub fn album_url(&self, short_or_url: &str) -> Result<Url, url::ParseError> {
+        let short_or_url = short_or_url.trim_end_matches('/');
+        if short_or_url.contains("://") {
+            Url::parse(short_or_url)
+        } else if short_or_url.contains('/') {
+            Url::parse(&format!("{}://{short_or_url}", self.base.scheme()))
+        } else {
+            Ok(self.album(short_or_url))
+        }
+    }
+
+    /// Extracts the slug(`short.ext`) of a file url, `None` if `url` is not an uploaded file.
+    pub fn file_slug<'a>(&self, url: &'a Url) -> Option<&'a str> {
+        if !same_host(url, &self.files) {
+            return None;
+        }
+        url.path()
+            .strip_prefix(self.files.path())
+            .filter(|x| !x.is_empty())
+    }
+}
+
+fn join(base: &Url, path: &str) -> Url {
+    base.join(path)
+        .expect("base url is validated when configured")
+}
+
+fn same_host(a: &Url, b: &Url) -> bool {
+    a.host_str() == b.host_str() && a.port_or_known_default() == b.port_or_known_default()
+}
+
+fn with_trailing_slash(mut url: Url) -> Url {
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    url
+}
@@ -1,6 +1,7 @@
-use std::path::PathBuf;
+use std::{net::SocketAddr, path::PathBuf};
 
 use error_set::error_set;
+use serde::Serialize;
 use url::Url;
 
 #[macro_export]
@@ -16,14 +17,64 @@ error_set! {
     AppError = {
         #[display("Fails to output multi-progress bar")]
         MultiProgressOutputError(std::io::Error),
+        #[display("Fails to write to stdout")]
+        WriteOutput(std::io::Error),
         #[display("Fails to translate to json")]
         JsonTranslationError(serde_json::Error),
         #[display("Invalid url. '{url}'")]
         InvalidUrl(url::ParseError) { url: String },
-    }|| AlbumError || UserError;
+        #[display("Url '{url}' can not be used as a base url")]
+        InvalidBaseUrl { url: Url },
+        #[display("Litterbox uploads can not be added to an album")]
+        AlbumWithLitterbox,
+        #[display("Invalid regex `{pattern}`")]
+        InvalidRegex(regex::Error) { pattern: String },
+        #[display("`--{first}` can not be combined with `--{second}`")]
+        ConflictingOptions { first: &'static str, second: &'static str },
+        #[display("`--format template` requires `--template`")]
+        LackOfTemplate,
+        #[display("Fails to open '{url}'")]
+        OpenUrl(std::io::Error) { url: String },
+        #[display("Fails to watch `{}`", dir.display())]
+        Watch(notify::Error) { dir: PathBuf },
+        #[display("Fails to write export `{}`", file.display())]
+        WriteExport(std::io::Error) { file: PathBuf },
+        #[display("Fails to write uploader `{}`", file.display())]
+        WriteUploader(std::io::Error) { file: PathBuf },
+        #[display("Fails to read stdin")]
+        ReadStdin(std::io::Error),
+        #[display("Fails to read tokens `{}`", file.display())]
+        ReadTokens(std::io::Error) { file: PathBuf },
+        #[display("Fails to listen on {address}")]
+        BindGateway(std::io::Error) { address: SocketAddr },
+        #[display("Refuses to listen on {address} without a token, pass `--token` or `--token-file`")]
+        UnprotectedGateway { address: SocketAddr },
+        #[display("Gateway stopped unexpectedly")]
+        ServeGateway(std::io::Error),
+        #[display("Fails to draw the terminal interface")]
+        Terminal(std::io::Error),
+    }|| AlbumError || UserError || ConfigError || DownloadError || HistoryError || CacheError;
 
-    AlbumError = HtmlParsingError || NetworkError;
-    UserError = InnerUserError || NetworkError || KeyringError || HtmlParsingError || UploadFileError;
+    AlbumError = {
+        #[display("Invalid album url. '{url}'")]
+        InvalidAlbumUrl(url::ParseError) { url: String },
+        #[display("Album request returns non 200 error code: '{code}'.{}", ("\nReason: ".to_string() + reason))]
+        AlbumRequest { code: reqwest::StatusCode, reason: String },
+    } || HtmlParsingError || NetworkError;
+    UserError = InnerUserError || AlbumError || NetworkError || KeyringError || HtmlParsingError || UploadFileError;
+
+    DownloadError = {
+        #[display("Fails to write downloaded file `{}`", file.display())]
+        WriteFile(std::io::Error) { file: PathBuf },
+        #[display("Fails to remove `{}`", file.display())]
+        RemoveFile(std::io::Error) { file: PathBuf },
+        #[display("Fails to read pull state `{}`", file.display())]
+        ReadState(std::io::Error) { file: PathBuf },
+        #[display("Pull state `{}` is not valid json", file.display())]
+        InvalidState(serde_json::Error) { file: PathBuf },
+        #[display("`{}` belongs to a pull of '{album}', use another directory", file.display())]
+        StateOfOtherAlbum { file: PathBuf, album: Url },
+    } || AlbumError;
 
     HtmlParsingError = {
         #[display("Fails to parse html.")]
@@ -51,6 +102,12 @@ error_set! {
         ErrorCode(reqwest::Error),
         #[display("Downloaded file can not be turned into text")]
         InvalidText(reqwest::Error),
+        #[display("Invalid proxy url. '{proxy}'")]
+        InvalidProxy(reqwest::Error) { proxy: String },
+        #[display("Fails to read CA bundle `{}`", path.display())]
+        ReadCaBundle(std::io::Error) { path: PathBuf },
+        #[display("Fails to parse CA bundle `{}`", path.display())]
+        InvalidCaBundle(reqwest::Error) { path: PathBuf },
     };
 
     KeyringError = {
@@ -64,7 +121,46 @@ error_set! {
         FailureSettingVariable(keyring::Error)
     };
 
+    HistoryError = {
+        #[display("Fails to determine the data directory of this system")]
+        LackOfDataDir,
+        #[display("Fails to read history `{}`", path.display())]
+        ReadHistory(std::io::Error) { path: PathBuf },
+        #[display("Fails to parse line {line} of history `{}`", path.display())]
+        ParseHistory(serde_json::Error) { path: PathBuf, line: usize },
+        #[display("Fails to write history `{}`", path.display())]
+        WriteHistory(std::io::Error) { path: PathBuf },
+    };
+
+    CacheError = {
+        #[display("Fails to determine the cache directory of this system")]
+        LackOfCacheDir,
+        #[display("Fails to write listing cache `{}`", path.display())]
+        WriteCache(std::io::Error) { path: PathBuf },
+    };
+
+    ConfigError = {
+        #[display("Fails to determine the config directory of this system")]
+        LackOfConfigDir,
+        #[display("Fails to read config file `{}`", path.display())]
+        ReadConfig(std::io::Error) { path: PathBuf },
+        #[display("Fails to parse config file `{}`", path.display())]
+        ParseConfig(serde_json::Error) { path: PathBuf },
+        #[display("Fails to serialize config for `{}`", path.display())]
+        SerializeConfig(serde_json::Error) { path: PathBuf },
+        #[display("Fails to write config file `{}`", path.display())]
+        WriteConfig(std::io::Error) { path: PathBuf },
+        #[display("Unknown config key `{key}`")]
+        UnknownConfigKey { key: String },
+        #[display("Invalid value `{value}` for config key `{key}`")]
+        InvalidConfigValue { key: String, value: String },
+        #[display("Unusable value `{value}` for config key `{key}`")]
+        UnusableConfigValue(NetworkError) { key: String, value: String },
+    };
+
     InnerUserError = {
+        #[display("No account configured, please provide credentials to the client!")]
+        LackOfCredentials,
         #[display("Fails to create authenticated client with {username}")]
         AuthenticatedClientCreation(NetworkError) {
             username: String,
@@ -73,6 +169,8 @@ error_set! {
         InvalidSlug { slug: String },
         #[display("Fails to parse a short from url: {url}")]
         ShortParsing { url: Url },
+        #[display("Api request returns non 200 error code: '{code}'.{}", ("\nReason: ".to_string() + reason))]
+        ApiRequest { code: reqwest::StatusCode, reason: String },
     };
 
     UploadFileError = {
@@ -81,7 +179,110 @@ error_set! {
         #[display("Request returns non 200 error code: '{code}'.{}", ("\nReason: ".to_string() + reason))]
         InvalidResponseWithCode { code: reqwest::StatusCode, reason: String },
         #[display("Failed to determine filename for upload")]
-        InvalidFilename
+        InvalidFilename,
+        #[display("Invalid exclude pattern `{pattern}`")]
+        InvalidGlob(globset::Error) { pattern: String },
+        #[display("Fails to walk directory `{}`", dir.display())]
+        WalkDirectory(walkdir::Error) { dir: PathBuf },
+        #[display("Fails to strip metadata from `{}`. Reason: {reason}", file.display())]
+        MalformedImage { file: PathBuf, reason: &'static str },
     } || NetworkError;
 
 }
+
+/// Which category of `AppError` an error belongs to, for callers that branch on the kind of failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// misuse of the command line
+    App,
+    Config,
+    Keyring,
+    /// missing credentials, failed logins and files not owned by the account
+    User,
+    Network,
+    HtmlParsing,
+    UploadFile,
+    Album,
+    Download,
+    History,
+    Cache,
+    /// requests to the user api that catbox rejects
+    Api,
+    /// reading or writing local streams and files, e.g. stdout, stdin or an export
+    Io,
+}
+
+impl AppError {
+    pub const fn kind(&self) -> ErrorKind {
+        match self {
+            Self::InvalidUrl { .. }
+            | Self::InvalidBaseUrl { .. }
+            | Self::AlbumWithLitterbox { .. }
+            | Self::InvalidRegex { .. }
+            | Self::ConflictingOptions { .. }
+            | Self::LackOfTemplate { .. }
+            | Self::UnprotectedGateway { .. } => ErrorKind::App,
+            Self::MultiProgressOutputError { .. }
+            | Self::WriteOutput { .. }
+            | Self::JsonTranslationError { .. }
+            | Self::OpenUrl { .. }
+            | Self::Watch { .. }
+            | Self::WriteExport { .. }
+            | Self::WriteUploader { .. }
+            | Self::ReadStdin { .. }
+            | Self::ReadTokens { .. }
+            | Self::Terminal { .. } => ErrorKind::Io,
+            Self::LackOfConfigDir { .. }
+            | Self::ReadConfig { .. }
+            | Self::ParseConfig { .. }
+            | Self::SerializeConfig { .. }
+            | Self::WriteConfig { .. }
+            | Self::UnknownConfigKey { .. }
+            | Self::InvalidConfigValue { .. }
+            | Self::UnusableConfigValue { .. } => ErrorKind::Config,
+            Self::KeyringInitilization { .. }
+            | Self::LackOfPassword { .. }
+            | Self::LackOfUser { .. }
+            | Self::FailureSettingVariable { .. } => ErrorKind::Keyring,
+            Self::LackOfCredentials { .. }
+            | Self::AuthenticatedClientCreation { .. }
+            | Self::InvalidSlug { .. }
+            | Self::ShortParsing { .. } => ErrorKind::User,
+            Self::ClientCreation { .. }
+            | Self::DownloadRequest { .. }
+            | Self::ErrorCode { .. }
+            | Self::InvalidText { .. }
+            | Self::InvalidProxy { .. }
+            | Self::ReadCaBundle { .. }
+            | Self::InvalidCaBundle { .. }
+            | Self::BindGateway { .. }
+            | Self::ServeGateway { .. } => ErrorKind::Network,
+            Self::InvalidHtml { .. }
+            | Self::LackOfNodeid { .. }
+            | Self::LackOfContainer { .. }
+            | Self::LackOfChildren { .. }
+            | Self::LackOfSrc { .. }
+            | Self::LackOfUserHash { .. }
+            | Self::Utf8Incompatiable { .. } => ErrorKind::HtmlParsing,
+            Self::ReadFile { .. }
+            | Self::InvalidResponseWithCode { .. }
+            | Self::InvalidFilename { .. }
+            | Self::InvalidGlob { .. }
+            | Self::WalkDirectory { .. }
+            | Self::MalformedImage { .. } => ErrorKind::UploadFile,
+            Self::InvalidAlbumUrl { .. } | Self::AlbumRequest { .. } => ErrorKind::Album,
+            Self::ApiRequest { .. } => ErrorKind::Api,
+            Self::WriteFile { .. }
+            | Self::RemoveFile { .. }
+            | Self::ReadState { .. }
+            | Self::InvalidState { .. }
+            | Self::StateOfOtherAlbum { .. } => ErrorKind::Download,
+            Self::LackOfDataDir
+            | Self::ReadHistory { .. }
+            | Self::ParseHistory { .. }
+            | Self::WriteHistory { .. } => ErrorKind::History,
+            Self::LackOfCacheDir | Self::WriteCache { .. } => ErrorKind::Cache,
+        }
+    }
+}
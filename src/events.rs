@@ -0,0 +1,168 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use catbox::{
+    progress::{ProgressReporter, TaskId, TaskKind},
+    upload::Uploaded,
+    ErrorKind,
+};
+use serde::Serialize;
+
+/// Minimum time between two `progress` events of the same upload.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// progress bars on stderr, `path: url` per upload
+    #[default]
+    Text,
+    /// one json event per line on stdout
+    Jsonl,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(Self::Text),
+            "jsonl" => Ok(Self::Jsonl),
+            s => Err(format!(
+                "invalid output `{s}` (expected one of: text, jsonl)"
+            )),
+        }
+    }
+}
+
+/// A line of `--output=jsonl`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    UploadStarted {
+        path: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        total: Option<u64>,
+    },
+    Progress {
+        path: &'a str,
+        sent: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        total: Option<u64>,
+    },
+    UploadFinished {
+        path: &'a str,
+        #[serde(flatten)]
+        uploaded: &'a Uploaded,
+    },
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        path: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        kind: Option<ErrorKind>,
+        message: &'a str,
+    },
+}
+
+impl Event<'_> {
+    /// Writes the event as a single line to stdout.
+    pub fn emit(&self) -> io::Result<()> {
+        let mut line = serde_json::to_vec(self)?;
+        line.push(b'\n');
+
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(&line)?;
+        stdout.flush()
+    }
+}
+
+struct Upload {
+    path: String,
+    total: Option<u64>,
+    sent: u64,
+    last_emitted: Instant,
+}
+
+/// Reports uploads as `Event`s, every other task(downloads, logging in, fetching pages) is skipped.
+#[derive(Default)]
+pub struct JsonlReporter {
+    uploads: Mutex<HashMap<TaskId, Upload>>,
+    /// whether an upload was reported as failed, so the error isn't reported twice
+    failed: AtomicBool,
+}
+
+impl JsonlReporter {
+    pub fn has_failed(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    fn take(&self, task: TaskId) -> Option<Upload> {
+        self.uploads.lock().expect("uploads poisoned").remove(&task)
+    }
+}
+
+// a closed stdout can't be reported anywhere, so failing to emit is ignored
+impl ProgressReporter for JsonlReporter {
+    fn started(&self, task: TaskId, kind: TaskKind, label: &str, total: Option<u64>) {
+        if kind != TaskKind::Upload {
+            return;
+        }
+
+        let _ = Event::UploadStarted { path: label, total }.emit();
+
+        self.uploads.lock().expect("uploads poisoned").insert(
+            task,
+            Upload {
+                path: label.to_owned(),
+                total,
+                sent: 0,
+                last_emitted: Instant::now(),
+            },
+        );
+    }
+
+    fn bytes_sent(&self, task: TaskId, bytes: u64) {
+        let mut uploads = self.uploads.lock().expect("uploads poisoned");
+        let Some(upload) = uploads.get_mut(&task) else {
+            return;
+        };
+
+        upload.sent += bytes;
+
+        if upload.total.is_some_and(|x| upload.sent >= x)
+            || upload.last_emitted.elapsed() >= PROGRESS_INTERVAL
+        {
+            upload.last_emitted = Instant::now();
+            let _ = Event::Progress {
+                path: &upload.path,
+                sent: upload.sent,
+                total: upload.total,
+            }
+            .emit();
+        }
+    }
+
+    fn finished(&self, task: TaskId) {
+        // `upload_finished` is emitted by the caller, which knows the url
+        self.take(task);
+    }
+
+    fn failed(&self, task: TaskId, error: &str) {
+        if let Some(upload) = self.take(task) {
+            self.failed.store(true, Ordering::Relaxed);
+            let _ = Event::Error {
+                path: Some(&upload.path),
+                kind: None,
+                message: error,
+            }
+            .emit();
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::{fmt::Write, str::FromStr};
+
+use crate::{
+    album::{Album, Files},
+    file::{format_size, UploadedFile, DATE_FORMAT},
+    link::{escape_html, Embed},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// self-contained gallery page
+    Html,
+    /// playlist of the video and audio files
+    M3u,
+    Csv,
+    Json,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "html" => Ok(Self::Html),
+            "m3u" | "m3u8" => Ok(Self::M3u),
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            s => Err(format!(
+                "invalid export format `{s}` (expected one of: html, m3u, csv, json)"
+            )),
+        }
+    }
+}
+
+impl ExportFormat {
+    /// Renders `album` and its `files`, see `Files::with_metadata` for names and sizes.
+    pub fn render(self, album: &Album, files: &Files) -> Result<String, serde_json::Error> {
+        match self {
+            Self::Html => Ok(html(album, files)),
+            Self::M3u => Ok(m3u(files)),
+            Self::Csv => Ok(csv(files)),
+            Self::Json => serde_json::to_string_pretty(&serde_json::json!({
+                "album": album,
+                "files": files.files,
+            }))
+            .map(|x| x + "\n"),
+        }
+    }
+}
+
+fn name(file: &UploadedFile) -> String {
+    file.name.clone().unwrap_or_else(|| file.slug())
+}
+
+const STYLE: &str = "\
+body{font-family:system-ui,sans-serif;margin:2rem;background:#111;color:#eee}\
+a{color:#8cf}\
+header p{color:#aaa}\
+main{display:grid;grid-template-columns:repeat(auto-fill,minmax(240px,1fr));gap:1rem}\
+figure{margin:0;background:#1c1c1c;border-radius:8px;overflow:hidden}\
+figure img,figure video{display:block;width:100%;height:200px;object-fit:cover;background:#000}\
+figure audio{display:block;width:100%;margin-top:150px}\
+figure .file{display:flex;height:200px;align-items:center;justify-content:center;font-size:2rem;color:#666}\
+figcaption{padding:.5rem;font-size:.9rem;overflow-wrap:anywhere}\
+figcaption span{color:#aaa}";
+
+fn html(album: &Album, files: &Files) -> String {
+    let title = escape_html(album.title.as_deref().unwrap_or(album.url.as_str()));
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<header>\n<h1>{title}</h1>\n"
+    );
+
+    if let Some(description) = album.description.as_deref().filter(|x| !x.is_empty()) {
+        let _ = writeln!(page, "<p>{}</p>", escape_html(description));
+    }
+    let mut details = vec![format!("{} files", files.files.len())];
+    if let Some(created) = album.created {
+        details.push(format!("created {}", created.format(DATE_FORMAT)));
+    }
+    let url = escape_html(album.url.as_str());
+    let _ = writeln!(
+        page,
+        "<p>{} &middot; <a href=\"{url}\">{url}</a></p>\n</header>\n<main>",
+        details.join(" &middot; ")
+    );
+
+    for file in &files.files {
+        let url = escape_html(file.url.as_str());
+        let name = escape_html(&name(file));
+        let preview = match Embed::of(file) {
+            Some(Embed::Image) => {
+                format!("<a href=\"{url}\"><img src=\"{url}\" alt=\"{name}\" loading=\"lazy\"></a>")
+            }
+            Some(Embed::Video) => {
+                format!("<video src=\"{url}\" controls preload=\"metadata\"></video>")
+            }
+            Some(Embed::Audio) => {
+                format!("<audio src=\"{url}\" controls preload=\"none\"></audio>")
+            }
+            None => format!(
+                "<a class=\"file\" href=\"{url}\">.{}</a>",
+                escape_html(file.extension.as_deref().unwrap_or_default())
+            ),
+        };
+        let size = file
+            .size
+            .map(|x| format!(" <span>{}</span>", format_size(x)))
+            .unwrap_or_default();
+        let _ = writeln!(
+            page,
+            "<figure>{preview}<figcaption><a href=\"{url}\" download>{name}</a>{size}</figcaption></figure>"
+        );
+    }
+
+    page.push_str("</main>\n</body>\n</html>\n");
+    page
+}
+
+fn m3u(files: &Files) -> String {
+    let mut playlist = String::from("#EXTM3U\n");
+    for file in &files.files {
+        if matches!(Embed::of(file), Some(Embed::Video | Embed::Audio)) {
+            // a line break in the name would start an entry of its own
+            let name = name(file).replace(['\r', '\n'], " ");
+            let _ = writeln!(playlist, "#EXTINF:-1,{name}\n{}", file.url);
+        }
+    }
+    playlist
+}
+
+fn csv(files: &Files) -> String {
+    fn field(value: &str) -> String {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_owned()
+        }
+    }
+
+    let mut table = String::from("url,name,size,date,short,extension\n");
+    for file in &files.files {
+        let _ = writeln!(
+            table,
+            "{},{},{},{},{},{}",
+            field(file.url.as_str()),
+            field(file.name.as_deref().unwrap_or_default()),
+            file.size.map(|x| x.to_string()).unwrap_or_default(),
+            file.date
+                .map(|x| x.format(DATE_FORMAT).to_string())
+                .unwrap_or_default(),
+            field(&file.short),
+            field(file.extension.as_deref().unwrap_or_default()),
+        );
+    }
+    table
+}
@@ -0,0 +1,100 @@
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// Format of the upload dates shown on the account view page.
+pub const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A file listed on the account view page.
+///
+/// `name`, `size` and `date` are `None` when the page doesn't show them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadedFile {
+    pub url: Url,
+    /// name of the file when it was uploaded
+    pub name: Option<String>,
+    /// size in bytes
+    pub size: Option<u64>,
+    /// upload date, in the timezone of the catbox server
+    pub date: Option<NaiveDateTime>,
+    /// slug without the extension, `w0v6bk` for `w0v6bk.webm`
+    pub short: String,
+    pub extension: Option<String>,
+}
+
+impl UploadedFile {
+    /// Creates an `UploadedFile` with only the fields derived from `url`.
+    pub fn new(url: Url) -> Self {
+        let slug = url
+            .path_segments()
+            .and_then(|mut x| x.next_back())
+            .unwrap_or_default();
+        let path = Path::new(slug);
+
+        let short = path
+            .file_stem()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let extension = path.extension().map(|x| x.to_string_lossy().into_owned());
+
+        Self {
+            url,
+            name: None,
+            size: None,
+            date: None,
+            short,
+            extension,
+        }
+    }
+
+    /// `short.extension`, the name catbox knows the file by.
+    pub fn slug(&self) -> String {
+        match &self.extension {
+            Some(extension) => format!("{}.{extension}", self.short),
+            None => self.short.clone(),
+        }
+    }
+}
+
+/// Parses a human readable size like `1.5 MB` or `300 B` into bytes.
+///
+/// Units are powers of 1024, matching how catbox prints them.
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|x: char| !(x.is_ascii_digit() || x == '.'))
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number = number.parse::<f64>().ok()?;
+
+    let exponent = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 1,
+        "M" | "MB" | "MIB" => 2,
+        "G" | "GB" | "GIB" => 3,
+        "T" | "TB" | "TIB" => 4,
+        _ => return None,
+    };
+
+    Some((number * 1024_f64.powi(exponent)).round() as u64)
+}
+
+/// Formats `bytes` the way `parse_size` reads them, e.g. `1.5 MB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
@@ -0,0 +1,188 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use regex::Regex;
+
+use crate::{errors::*, file::UploadedFile};
+
+/// How a file name is matched, against both the original name and the slug.
+#[derive(Debug, Clone)]
+pub enum NameMatcher {
+    /// case insensitive substring
+    Substring(String),
+    Regex(Regex),
+}
+
+impl NameMatcher {
+    pub fn substring(needle: &str) -> Self {
+        Self::Substring(needle.to_lowercase())
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self, AppError> {
+        Regex::new(pattern)
+            .map(Self::Regex)
+            .map_err(|source| AppError::InvalidRegex {
+                source,
+                pattern: pattern.to_owned(),
+            })
+    }
+
+    fn is_match(&self, name: &str) -> bool {
+        match self {
+            Self::Substring(needle) => name.to_lowercase().contains(needle),
+            Self::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// Which files to keep, every condition has to hold.
+///
+/// Files missing the metadata a condition needs(no date for `since`, ...) are dropped.
+#[derive(Debug, Clone, Default)]
+pub struct FileFilter {
+    /// keep files with any of these extensions, case insensitive, without the dot
+    pub extensions: Vec<String>,
+    pub name: Option<NameMatcher>,
+    /// uploaded at or after
+    pub since: Option<NaiveDateTime>,
+    /// uploaded at or before
+    pub until: Option<NaiveDateTime>,
+    /// at least this many bytes
+    pub min_size: Option<u64>,
+    /// at most this many bytes
+    pub max_size: Option<u64>,
+}
+
+impl FileFilter {
+    pub fn matches(&self, file: &UploadedFile) -> bool {
+        let extension = file.extension.as_deref().unwrap_or_default();
+
+        (self.extensions.is_empty()
+            || self
+                .extensions
+                .iter()
+                .any(|x| x.trim_start_matches('.').eq_ignore_ascii_case(extension)))
+            && self.name.as_ref().is_none_or(|x| {
+                x.is_match(&file.slug()) || file.name.as_deref().is_some_and(|y| x.is_match(y))
+            })
+            && self.since.is_none_or(|x| file.date.is_some_and(|y| y >= x))
+            && self.until.is_none_or(|x| file.date.is_some_and(|y| y <= x))
+            && self
+                .min_size
+                .is_none_or(|x| file.size.is_some_and(|y| y >= x))
+            && self
+                .max_size
+                .is_none_or(|x| file.size.is_some_and(|y| y <= x))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// original name, falling back to the slug
+    Name,
+    Date,
+    Size,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "name" => Ok(Self::Name),
+            "date" => Ok(Self::Date),
+            "size" => Ok(Self::Size),
+            s => Err(format!(
+                "invalid sort key `{s}` (expected one of: name, date, size)"
+            )),
+        }
+    }
+}
+
+impl SortKey {
+    /// Ascending order, files missing the key go last.
+    pub fn compare(self, a: &UploadedFile, b: &UploadedFile) -> Ordering {
+        fn missing_last<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+            match (a, b) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+
+        match self {
+            Self::Name => {
+                let name = |x: &UploadedFile| x.name.clone().unwrap_or_else(|| x.slug());
+                name(a).to_lowercase().cmp(&name(b).to_lowercase())
+            }
+            Self::Date => missing_last(a.date, b.date),
+            Self::Size => missing_last(a.size, b.size),
+        }
+    }
+}
+
+/// A filter plus an order, shared by every command working on a selection of files.
+///
+/// # Example
+///
+/// ```
+/// let query = FileQuery {
+///     filter: FileFilter { extensions: vec!["png".into()], ..Default::default() },
+///     sort: Some(SortKey::Size),
+///     reverse: true,
+/// };
+/// let biggest_pngs = query.apply(client.list_files().await?);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FileQuery {
+    pub filter: FileFilter,
+    /// keeps the listing order when `None`
+    pub sort: Option<SortKey>,
+    pub reverse: bool,
+}
+
+impl FileQuery {
+    /// Whether the order of the files depends on more than the listing order,
+    /// in which case every file has to be known before any is output.
+    pub const fn reorders(&self) -> bool {
+        self.sort.is_some() || self.reverse
+    }
+
+    pub fn apply(&self, files: impl IntoIterator<Item = UploadedFile>) -> Vec<UploadedFile> {
+        let mut files = files
+            .into_iter()
+            .filter(|x| self.filter.matches(x))
+            .collect::<Vec<_>>();
+
+        if let Some(sort) = self.sort {
+            files.sort_by(|a, b| sort.compare(a, b));
+        }
+        if self.reverse {
+            files.reverse();
+        }
+
+        files
+    }
+}
+
+/// Parses `YYYY-MM-DD` or `YYYY-MM-DD HH:MM:SS`.
+///
+/// A bare date means the start of that day, or its end when `end_of_day` is set,
+/// so `--until 2024-01-31` includes the whole 31st.
+pub fn parse_date(value: &str, end_of_day: bool) -> Result<NaiveDateTime, String> {
+    let value = value.trim();
+
+    NaiveDateTime::parse_from_str(value, crate::file::DATE_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|x| {
+                if end_of_day {
+                    x.and_time(NaiveTime::from_hms_opt(23, 59, 59).expect("valid time"))
+                } else {
+                    x.and_time(NaiveTime::MIN)
+                }
+            })
+        })
+        .map_err(|_| format!("invalid date `{value}` (expected YYYY-MM-DD or YYYY-MM-DD HH:MM:SS)"))
+}
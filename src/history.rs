@@ -0,0 +1,150 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Local, NaiveDateTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    errors::*,
+    upload::{LitterExpiry, Uploaded},
+};
+
+/// One upload recorded in the history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// local time of the upload
+    pub date: NaiveDateTime,
+    /// command that uploaded the file, e.g. `watch`
+    pub source: String,
+    /// the uploaded local file, `None` when there was none
+    pub path: Option<PathBuf>,
+    pub url: String,
+    /// `None` for url uploads, which catbox downloaded itself
+    pub size: Option<u64>,
+    pub sha256: Option<String>,
+    /// album the file was added to
+    pub album: Option<Url>,
+    /// when a Litterbox upload is gone, `None` for catbox uploads
+    pub expires: Option<NaiveDateTime>,
+}
+
+impl HistoryEntry {
+    /// An entry for a catbox upload that just finished.
+    pub fn new(source: impl Into<String>, path: Option<PathBuf>, uploaded: &Uploaded) -> Self {
+        Self {
+            date: Local::now().naive_local(),
+            source: source.into(),
+            path,
+            url: uploaded.url.clone(),
+            size: Some(uploaded.size),
+            sha256: Some(uploaded.sha256.clone()),
+            album: None,
+            expires: None,
+        }
+    }
+
+    /// An entry for a url upload, catbox fetched `url` itself so only the new url is known.
+    pub fn remote(source: impl Into<String>, url: String) -> Self {
+        Self {
+            date: Local::now().naive_local(),
+            source: source.into(),
+            path: None,
+            url,
+            size: None,
+            sha256: None,
+            album: None,
+            expires: None,
+        }
+    }
+
+    pub fn album(mut self, album: Url) -> Self {
+        self.album = Some(album);
+        self
+    }
+
+    /// Marks the entry as a Litterbox upload, expiring `expiry` after its date.
+    pub fn litterbox(mut self, expiry: LitterExpiry) -> Self {
+        self.expires = Some(self.date + TimeDelta::hours(expiry.hours()));
+        self
+    }
+}
+
+/// Append-only ledger of uploads, one json object per line.
+///
+/// Lives in `<data dir>/catbox-cli/history.jsonl`, unless overridden by `CBX_HISTORY`.
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    pub fn open() -> Result<Self, HistoryError> {
+        if let Some(path) = std::env::var_os("CBX_HISTORY") {
+            return Ok(Self::at(path));
+        }
+        Ok(Self::at(
+            dirs::data_dir()
+                .ok_or(HistoryError::LackOfDataDir)?
+                .join("catbox-cli")
+                .join("history.jsonl"),
+        ))
+    }
+
+    pub fn at(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, entry: &HistoryEntry) -> Result<(), HistoryError> {
+        let write_error = |source| HistoryError::WriteHistory {
+            source,
+            path: self.path.clone(),
+        };
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(write_error)?;
+        }
+
+        let mut line = serde_json::to_string(entry).expect("history entry serializes");
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut x| x.write_all(line.as_bytes()))
+            .map_err(write_error)
+    }
+
+    /// Every recorded upload, oldest first. A missing history is an empty one.
+    pub fn entries(&self) -> Result<Vec<HistoryEntry>, HistoryError> {
+        let text = match std::fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(source) => {
+                return Err(HistoryError::ReadHistory {
+                    source,
+                    path: self.path.clone(),
+                })
+            }
+        };
+
+        text.lines()
+            .enumerate()
+            .filter(|(_, x)| !x.trim().is_empty())
+            .map(|(i, x)| {
+                serde_json::from_str(x).map_err(|source| HistoryError::ParseHistory {
+                    source,
+                    path: self.path.clone(),
+                    line: i + 1,
+                })
+            })
+            .collect()
+    }
+}
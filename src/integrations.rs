@@ -0,0 +1,87 @@
+use std::str::FromStr;
+
+use catbox::{
+    endpoints::Endpoints,
+    upload::{LitterExpiry, UploadTarget},
+};
+use serde_json::{json, Value};
+
+/// A ShareX custom uploader(`.sxcu`) sending files to `target`.
+pub fn sharex_uploader(endpoints: &Endpoints, target: &UploadTarget) -> Value {
+    let (name, url, arguments) = match target {
+        UploadTarget::Catbox { user_hash } => (
+            "catbox.moe".to_owned(),
+            endpoints.api(),
+            json!({ "reqtype": "fileupload", "userhash": user_hash }),
+        ),
+        UploadTarget::Litterbox { expiry } => (
+            format!("Litterbox ({})", expiry.as_str()),
+            endpoints.litter_api(),
+            json!({ "reqtype": "fileupload", "time": expiry.as_str() }),
+        ),
+    };
+
+    json!({
+        "Version": "14.1.0",
+        "Name": name,
+        "DestinationType": "ImageUploader, TextUploader, FileUploader",
+        "RequestMethod": "POST",
+        "RequestURL": url.as_str(),
+        "Body": "MultipartFormData",
+        "Arguments": arguments,
+        "FileFormName": "fileToUpload",
+        "URL": "{response}",
+    })
+}
+
+/// Screenshot tools `cbx integrations hook` knows how to pipe from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookTool {
+    Flameshot,
+    Grim,
+    Spectacle,
+}
+
+impl FromStr for HookTool {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "flameshot" => Ok(Self::Flameshot),
+            "grim" => Ok(Self::Grim),
+            "spectacle" => Ok(Self::Spectacle),
+            s => Err(format!(
+                "invalid screenshot tool `{s}` (expected one of: flameshot, grim, spectacle)"
+            )),
+        }
+    }
+}
+
+impl HookTool {
+    /// Command writing a PNG of a selected region to stdout.
+    const fn capture(self) -> &'static str {
+        match self {
+            Self::Flameshot => "flameshot gui --raw",
+            Self::Grim => r#"grim -g "$(slurp)" -"#,
+            Self::Spectacle => "spectacle --background --region --nonotify --output /dev/stdout",
+        }
+    }
+}
+
+/// A shell function taking a screenshot with `tool`, uploading it through `cbx file upload -` and
+/// printing its url.
+pub fn hook_script(tool: HookTool, expiry: Option<LitterExpiry>) -> String {
+    let litterbox = expiry
+        .map(|x| format!(" --use-litterbox --expiry {}", x.as_str()))
+        .unwrap_or_default();
+
+    format!(
+        "# Add to your shell rc file, then bind `cbx-screenshot` to a key.\n\
+         # Pipe the url into wl-copy or xclip to put it on the clipboard.\n\
+         cbx-screenshot() {{\n    \
+         {capture} | cbx file upload{litterbox} --format url \
+         --name \"screenshot-$(date +%Y%m%d-%H%M%S).png\" -\n\
+         }}\n",
+        capture = tool.capture(),
+    )
+}
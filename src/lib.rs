@@ -0,0 +1,22 @@
+pub mod album;
+pub mod archive;
+pub(crate) mod authentication;
+pub mod cache;
+pub mod client;
+pub mod config;
+pub mod credentials;
+pub mod endpoints;
+mod errors;
+pub mod export;
+pub mod file;
+pub mod filter;
+pub mod history;
+pub mod link;
+pub mod metadata;
+pub mod network;
+pub mod progress;
+pub mod sync;
+pub mod upload;
+pub mod user;
+pub use client::{CatboxClient, CatboxClientBuilder};
+pub use errors::*;
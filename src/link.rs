@@ -0,0 +1,166 @@
+use std::str::FromStr;
+
+use crate::file::{format_size, UploadedFile};
+
+const IMAGE_EXTENSIONS: [&str; 9] = [
+    "png", "jpg", "jpeg", "gif", "webp", "avif", "bmp", "svg", "apng",
+];
+const VIDEO_EXTENSIONS: [&str; 5] = ["mp4", "webm", "mov", "m4v", "ogv"];
+const AUDIO_EXTENSIONS: [&str; 7] = ["mp3", "ogg", "oga", "opus", "flac", "wav", "m4a"];
+
+/// How a file is written out when it is shared.
+///
+/// Images, videos and audio get embed syntax where the format has one, other files a plain link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkFormat {
+    Url,
+    Markdown,
+    Html,
+    Bbcode,
+    /// `{name}`, `{url}`, `{size}` and `{short}` are replaced, see `LinkFormat::render`
+    Template(String),
+}
+
+impl FromStr for LinkFormat {
+    type Err = String;
+
+    /// `template` parses to an empty template, to be filled in with the one given separately.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "url" => Ok(Self::Url),
+            "markdown" | "md" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            "bbcode" => Ok(Self::Bbcode),
+            "template" => Ok(Self::Template(String::new())),
+            s => Err(format!(
+                "invalid link format `{s}` (expected one of: url, markdown, html, bbcode, template)"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Embed {
+    Image,
+    Video,
+    Audio,
+}
+
+impl Embed {
+    /// How a file with this extension is embedded, `None` when it can only be linked.
+    pub fn of(file: &UploadedFile) -> Option<Self> {
+        let extension = file.extension.as_deref()?.to_ascii_lowercase();
+        if IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+            Some(Self::Image)
+        } else if VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+            Some(Self::Video)
+        } else if AUDIO_EXTENSIONS.contains(&extension.as_str()) {
+            Some(Self::Audio)
+        } else {
+            None
+        }
+    }
+}
+
+impl LinkFormat {
+    /// Renders `file`, using the slug when its original name is unknown.
+    ///
+    /// In templates a missing size becomes an empty string.
+    pub fn render(&self, file: &UploadedFile) -> String {
+        let url = file.url.as_str();
+        let name = file.name.clone().unwrap_or_else(|| file.slug());
+
+        match (self, Embed::of(file)) {
+            (Self::Url, _) => url.to_owned(),
+            (Self::Markdown, Some(Embed::Image)) => {
+                format!("![{}]({url})", escape_markdown(&name))
+            }
+            // markdown has no video syntax, GitHub and most renderers accept the html tag
+            (Self::Markdown, Some(Embed::Video)) => {
+                format!(r#"<video src="{}" controls></video>"#, escape_html(url))
+            }
+            (Self::Markdown, Some(Embed::Audio) | None) => {
+                format!("[{}]({url})", escape_markdown(&name))
+            }
+            (Self::Html, Some(Embed::Image)) => format!(
+                r#"<img src="{}" alt="{}">"#,
+                escape_html(url),
+                escape_html(&name)
+            ),
+            (Self::Html, Some(Embed::Video)) => {
+                format!(r#"<video src="{}" controls></video>"#, escape_html(url))
+            }
+            (Self::Html, Some(Embed::Audio)) => {
+                format!(r#"<audio src="{}" controls></audio>"#, escape_html(url))
+            }
+            (Self::Html, None) => format!(
+                r#"<a href="{}">{}</a>"#,
+                escape_html(url),
+                escape_html(&name)
+            ),
+            (Self::Bbcode, Some(Embed::Image)) => format!("[img]{url}[/img]"),
+            (Self::Bbcode, Some(Embed::Video)) => format!("[video]{url}[/video]"),
+            (Self::Bbcode, Some(Embed::Audio) | None) => {
+                format!("[url={url}]{}[/url]", escape_bbcode(&name))
+            }
+            (Self::Template(template), _) => fill(
+                template,
+                &[
+                    ("name", &name),
+                    ("url", url),
+                    ("size", &file.size.map(format_size).unwrap_or_default()),
+                    ("short", &file.short),
+                ],
+            ),
+        }
+    }
+}
+
+/// Replaces every `{key}` of `template` with its value in a single pass, so placeholders inside
+/// the values, e.g. a file named `{url}.txt`, are left as they are.
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let value = values.iter().find_map(|(key, value)| {
+            let after = rest.strip_prefix(key)?.strip_prefix('}')?;
+            Some((value, after))
+        });
+        match value {
+            Some((value, after)) => {
+                filled.push_str(value);
+                rest = after;
+            }
+            None => filled.push('{'),
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// Escapes text for use inside html elements and quoted attributes.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for x in text.chars() {
+        match x {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            x => escaped.push(x),
+        }
+    }
+    escaped
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('[', "\\[").replace(']', "\\]")
+}
+
+/// BBCode has no escapes, so brackets that would open or close a tag are dropped.
+fn escape_bbcode(text: &str) -> String {
+    text.replace(['[', ']'], "")
+}
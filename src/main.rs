@@ -1,177 +1,536 @@
-pub mod album;
-pub(crate) mod authentication;
 mod cli;
-mod errors;
-pub(crate) mod network;
-pub mod upload;
-pub mod user;
-pub use errors::*;
+mod completions;
+mod events;
+mod integrations;
+mod man;
+mod serve;
+mod tui;
+mod watch;
 
 use std::{
+    collections::HashSet,
     error::Error,
-    path::Path,
+    path::{Path, PathBuf},
     process::ExitCode,
     sync::{Arc, LazyLock},
     time::Duration,
 };
 
 use cli::*;
+use events::{Event, JsonlReporter, OutputFormat};
+use integrations::{hook_script, sharex_uploader};
+use serve::Gateway;
+use watch::{Destination, WatchRules};
 
-use album::Album;
+use bytes::Bytes;
+use catbox::{
+    archive::{walk_files, ArchiveOptions, Excludes},
+    cache::{ListingCache, DEFAULT_TTL},
+    config::Config,
+    credentials::{password_entry, username_entry},
+    endpoints::Endpoints,
+    ensure,
+    file::{format_size, UploadedFile, DATE_FORMAT},
+    filter::{FileFilter, FileQuery, NameMatcher},
+    history::{History, HistoryEntry},
+    link::LinkFormat,
+    metadata::ImageFormat,
+    network::NetworkOptions,
+    progress::{IndicatifReporter, ProgressReporter},
+    sync::{SyncAction, SyncPlan},
+    upload::{LitterExpiry, UploadOptions, UploadTarget, Uploaded},
+    AppError, CatboxClient, ErrorKind, UserError,
+};
 use futures_util::{FutureExt, StreamExt, TryStreamExt};
-use indicatif::{MultiProgress, ProgressBar};
-use keyring::Entry;
+use indicatif::MultiProgress;
+use rand::{rngs::StdRng, SeedableRng};
 use reqwest::Url;
-use tokio::sync::OnceCell;
-use user::User;
+use tokio::io::AsyncReadExt;
 
-use crate::upload::{upload_temp_file, LitterExpiry};
+pub static MULTI_PROGRESS: LazyLock<MultiProgress> = LazyLock::new(MultiProgress::new);
 
-fn get_username_entry() -> Result<Entry, KeyringError> {
-    Entry::new("catbox-cli", "username").map_err(KeyringError::KeyringInitilization)
+/// Prints a finished upload in the requested `output` format.
+///
+/// With `links`, text output is the formatted link on stdout, so it can be piped.
+fn report_upload(
+    path: &Path,
+    uploaded: &Uploaded,
+    output: OutputFormat,
+    links: Option<&LinkFormat>,
+) -> Result<(), AppError> {
+    match (output, links) {
+        (OutputFormat::Text, Some(links)) => {
+            let link = links.render(&uploaded_file(path, uploaded)?);
+            MULTI_PROGRESS.suspend(|| println!("{link}"));
+            Ok(())
+        }
+        (OutputFormat::Text, None) => MULTI_PROGRESS
+            .println(format!("{}: {}", path.display(), uploaded.url))
+            .map_err(AppError::MultiProgressOutputError),
+        (OutputFormat::Jsonl, _) => Event::UploadFinished {
+            path: &path.display().to_string(),
+            uploaded,
+        }
+        .emit()
+        .map_err(AppError::WriteOutput),
+    }
 }
 
-fn get_password_entry() -> Result<Entry, KeyringError> {
-    Entry::new("catbox-cli", "password").map_err(KeyringError::KeyringInitilization)
+/// An upload described like a listed file, named after the uploaded path.
+fn uploaded_file(path: &Path, uploaded: &Uploaded) -> Result<UploadedFile, AppError> {
+    let url = Url::parse(&uploaded.url).map_err(|source| AppError::InvalidUrl {
+        source,
+        url: uploaded.url.clone(),
+    })?;
+    Ok(UploadedFile {
+        name: path.file_name().map(|x| x.to_string_lossy().into_owned()),
+        size: Some(uploaded.size),
+        ..UploadedFile::new(url)
+    })
 }
 
-pub static USER_INSTANCE: LazyLock<Arc<UserInstance>> =
-    LazyLock::new(|| Arc::new(UserInstance::new()));
-
-pub static MULTI_PROGRESS: LazyLock<MultiProgress> = LazyLock::new(MultiProgress::new);
+/// Reads all of stdin, named `name` or `stdin` with the extension of a detected image.
+async fn read_stdin(name: Option<String>) -> Result<(String, Bytes), AppError> {
+    let mut bytes = Vec::new();
+    tokio::io::stdin()
+        .read_to_end(&mut bytes)
+        .await
+        .map_err(AppError::ReadStdin)?;
 
-#[derive(Default)]
-pub struct UserInstance {
-    cache: OnceCell<User>,
+    let name = name.unwrap_or_else(|| match ImageFormat::detect(&bytes) {
+        Some(format) => format!("stdin.{}", format.extension()),
+        None => "stdin".to_owned(),
+    });
+    Ok((name, bytes.into()))
 }
 
-impl UserInstance {
-    pub fn new() -> Self {
-        Self {
-            cache: OnceCell::new(),
+/// The link format chosen by `--format` or `--template`.
+fn link_format(
+    format: Option<LinkFormat>,
+    template: Option<String>,
+) -> Result<Option<LinkFormat>, AppError> {
+    match (format, template) {
+        (Some(LinkFormat::Template(_)) | None, Some(template)) => {
+            Ok(Some(LinkFormat::Template(template)))
         }
-    }
-    pub async fn get(&self) -> Result<&User, UserError> {
-        self.cache.get_or_try_init(User::new).await
+        (Some(LinkFormat::Template(_)), None) => Err(AppError::LackOfTemplate),
+        (Some(_), Some(_)) => Err(AppError::ConflictingOptions {
+            first: "format",
+            second: "template",
+        }),
+        (format, None) => Ok(format),
     }
 }
 
 pub async fn upload_files<T: AsRef<Path> + Sync>(
+    client: &CatboxClient,
     paths: impl AsRef<[T]> + Send,
-) -> Result<Vec<String>, AppError> {
-    let user = USER_INSTANCE.get().await?;
-
+    options: &UploadOptions,
+    output: OutputFormat,
+    links: Option<&LinkFormat>,
+) -> Result<Vec<Uploaded>, AppError> {
     futures_util::stream::iter(paths.as_ref())
         .map(AsRef::as_ref)
-        .map(|x| user.upload_file(x).map(move |y| Ok::<_, AppError>((x, y?))))
+        .map(|x| {
+            client
+                .upload(x, options)
+                .map(move |y| Ok::<_, AppError>((x, y?)))
+        })
         .buffer_unordered(5)
         .map(|x| {
-            let (path, url) = x?;
-            MULTI_PROGRESS
-                .println(format!("{}: {url}", path.display()))
-                .map_err(AppError::MultiProgressOutputError)?;
-            Ok(url)
+            let (path, uploaded) = x?;
+            report_upload(path, &uploaded, output, links)?;
+            Ok(uploaded)
         })
         .try_collect::<Vec<_>>()
         .await
 }
 
 pub async fn upload_temp_files<T: AsRef<Path> + Sync>(
+    client: &CatboxClient,
     paths: impl AsRef<[T]> + Send,
+    options: &UploadOptions,
     expiry: LitterExpiry,
-) -> Result<Vec<String>, AppError> {
+    output: OutputFormat,
+    links: Option<&LinkFormat>,
+) -> Result<Vec<Uploaded>, AppError> {
     futures_util::stream::iter(paths.as_ref())
         .map(AsRef::as_ref)
-        .map(|x| upload_temp_file(x, expiry).map(move |y| Ok::<_, AppError>((x, y?))))
+        .map(|x| {
+            client
+                .upload_temp(x, options, expiry)
+                .map(move |y| Ok::<_, AppError>((x, y?)))
+        })
         .buffer_unordered(5)
         .map(|x| {
-            let (path, url) = x?;
-            MULTI_PROGRESS
-                .println(format!("{}: {url}", path.display()))
-                .map_err(AppError::MultiProgressOutputError)?;
-            Ok(url)
+            let (path, uploaded) = x?;
+            report_upload(path, &uploaded, output, links)?;
+            Ok(uploaded)
         })
         .try_collect::<Vec<_>>()
         .await
 }
 
-pub async fn add_to_album(album: String, files: Vec<String>) -> Result<(), AppError> {
-    let user = USER_INSTANCE.get().await?;
+pub async fn add_to_album(
+    client: &CatboxClient,
+    album: &str,
+    files: Vec<String>,
+) -> Result<(), AppError> {
+    let album = client.album(album)?;
 
-    let album = get_album(album)?;
+    futures_util::stream::iter(files.iter().map(|x| client.file_slug(x)))
+        .map(|x| {
+            let album = &album;
+            async move { client.add_to_album(album, &x).await }
+        })
+        .buffer_unordered(5)
+        .try_collect::<Vec<_>>()
+        .await?;
+    Ok(())
+}
 
-    futures_util::stream::iter(files.into_iter().filter_map(|x| {
-        if x.contains("files.catbox.moe") {
-            Some(Url::parse(&x).ok()?.path_segments()?.next()?.to_owned())
-        } else {
-            Some(x)
+/// The `FileQuery` described by the filter and sort options of `file list`.
+fn file_query(list: &FileList) -> Result<FileQuery, AppError> {
+    ensure!(
+        list.name.is_none() || list.name_regex.is_none(),
+        AppError::ConflictingOptions {
+            first: "name",
+            second: "name-regex",
         }
-    }))
-    .map(move |x| {
-        let album = album.clone();
+    );
 
-        let pb = ProgressBar::new_spinner();
-        MULTI_PROGRESS.add(pb.clone());
+    let name = match (&list.name, &list.name_regex) {
+        (Some(name), _) => Some(NameMatcher::substring(name)),
+        (_, Some(pattern)) => Some(NameMatcher::regex(pattern)?),
+        (None, None) => None,
+    };
 
-        pb.enable_steady_tick(Duration::from_millis(100));
+    Ok(FileQuery {
+        filter: FileFilter {
+            extensions: list.ext.clone(),
+            name,
+            since: list.since,
+            until: list.until,
+            min_size: list.min_size,
+            max_size: list.max_size,
+        },
+        sort: list.sort,
+        reverse: list.reverse,
+    })
+}
 
-        pb.set_message(format!("Uploading '{x}' to album"));
+/// Prints `files` as a table for `file list --long`, newest first.
+fn print_file_table(files: &[UploadedFile]) {
+    let dates = files
+        .iter()
+        .map(|x| {
+            x.date
+                .map_or_else(|| "-".to_owned(), |x| x.format(DATE_FORMAT).to_string())
+        })
+        .collect::<Vec<_>>();
+    let sizes = files
+        .iter()
+        .map(|x| x.size.map_or_else(|| "-".to_owned(), format_size))
+        .collect::<Vec<_>>();
+    let slugs = files.iter().map(UploadedFile::slug).collect::<Vec<_>>();
 
-        async move {
-            let x = user.upload_to_album(&album, &x).await;
+    let date_width = dates.iter().map(String::len).max().unwrap_or(0).max(4);
+    let size_width = sizes.iter().map(String::len).max().unwrap_or(0).max(4);
+    let slug_width = slugs.iter().map(String::len).max().unwrap_or(0).max(4);
 
-            pb.finish_and_clear();
+    println!(
+        "{:date_width$}  {:>size_width$}  {:slug_width$}  NAME",
+        "DATE", "SIZE", "FILE"
+    );
+    for i in (0..files.len()).rev() {
+        println!(
+            "{:date_width$}  {:>size_width$}  {:slug_width$}  {}",
+            dates[i],
+            sizes[i],
+            slugs[i],
+            files[i].name.as_deref().unwrap_or("-")
+        );
+    }
+}
 
-            x
+/// Prints what `album sync` would do, one line per action.
+fn print_sync_plan(plan: &SyncPlan) {
+    for action in &plan.actions {
+        match action {
+            SyncAction::Upload { path } => println!("upload  {}", path.display()),
+            SyncAction::Replace { path, previous } => {
+                println!("replace {} ({})", path.display(), previous.slug());
+            }
+            SyncAction::Remove { file } => println!(
+                "remove  {} ({})",
+                file.slug(),
+                file.name.as_deref().unwrap_or_default()
+            ),
         }
-    })
-    .buffer_unordered(5)
-    .try_collect::<Vec<_>>()
-    .await?;
-    Ok(())
+    }
+    println!("{} unchanged", plan.unchanged.len());
 }
 
-fn get_album(album: String) -> Result<Album, AppError> {
-    let album = {
-        if album.contains("catbox.moe") {
-            Album::new(
-                Url::parse(&album).map_err(|source| AppError::InvalidUrl { source, url: album })?,
-            )
+/// Replaces every directory in `paths` with the files inside of it.
+fn expand_directories(paths: Vec<PathBuf>, excludes: &Excludes) -> Result<Vec<PathBuf>, AppError> {
+    let mut expanded = Vec::with_capacity(paths.len());
+    for path in paths {
+        if path.is_dir() {
+            expanded.extend(
+                walk_files(&path, excludes)?
+                    .into_iter()
+                    .map(|x| path.join(x)),
+            );
         } else {
-            let url = format!("https://catbox.moe/c/{album}");
-            Album::new(Url::parse(&url).map_err(|source| AppError::InvalidUrl { source, url })?)
+            expanded.push(path);
         }
-    };
-    Ok(album)
+    }
+    Ok(expanded)
+}
+
+fn env_url(name: &str) -> Result<Option<Url>, AppError> {
+    std::env::var(name)
+        .ok()
+        .filter(|x| !x.is_empty())
+        .map(|url| Url::parse(&url).map_err(|source| AppError::InvalidUrl { source, url }))
+        .transpose()
+}
+
+/// Resolves the endpoints, `--base-url` wins over the `CBX_BASE_URL`, `CBX_FILES_URL` and
+/// `CBX_LITTERBOX_URL` environment variables, which win over the config file.
+fn resolve_endpoints(base_url: Option<Url>, config: &Config) -> Result<Endpoints, AppError> {
+    let base = base_url
+        .or(env_url("CBX_BASE_URL")?)
+        .or_else(|| config.base_url.clone());
+    let files = env_url("CBX_FILES_URL")?.or_else(|| config.files_url.clone());
+    let litterbox = env_url("CBX_LITTERBOX_URL")?.or_else(|| config.litterbox_url.clone());
+
+    let mut endpoints = base.map_or_else(Endpoints::default, Endpoints::from_base_url);
+    if let Some(files) = files {
+        endpoints = endpoints.with_files(files);
+    }
+    if let Some(litterbox) = litterbox {
+        endpoints = endpoints.with_litterbox(litterbox);
+    }
+
+    for url in [&endpoints.base, &endpoints.files, &endpoints.litterbox] {
+        ensure!(
+            !url.cannot_be_a_base(),
+            AppError::InvalidBaseUrl { url: url.clone() }
+        );
+    }
+
+    Ok(endpoints)
+}
+
+/// Exit code of each `ErrorKind`, documented in the README.
+const fn exit_code(kind: ErrorKind) -> u8 {
+    match kind {
+        ErrorKind::App => 1,
+        ErrorKind::Config => 3,
+        ErrorKind::Keyring => 4,
+        ErrorKind::User => 5,
+        ErrorKind::Network => 6,
+        ErrorKind::HtmlParsing => 7,
+        ErrorKind::UploadFile => 8,
+        ErrorKind::Album => 9,
+        ErrorKind::Download => 10,
+        ErrorKind::History => 11,
+        ErrorKind::Cache => 12,
+        ErrorKind::Api => 13,
+        ErrorKind::Io => 14,
+    }
+}
+
+/// `argh::from_env`, except that `-`(stdin) is accepted as a positional argument.
+///
+/// argh treats `-` as an unknown flag, so it is moved behind a `--`.
+fn parse_args() -> Cli {
+    let mut args = std::env::args().collect::<Vec<_>>();
+    let end = args.iter().position(|x| x == "--").unwrap_or(args.len());
+    if args[..end].iter().any(|x| x == "-") {
+        let mut positional = args.split_off(end);
+        args.retain(|x| x != "-");
+        if positional.is_empty() {
+            positional.push("--".to_owned());
+        }
+        positional.push("-".to_owned());
+        args.extend(positional);
+    }
+
+    let command = Path::new(&args[0])
+        .file_name()
+        .map_or("cbx".into(), |x| x.to_string_lossy());
+    let args = args[1..].iter().map(String::as_str).collect::<Vec<_>>();
+    match <Cli as argh::FromArgs>::from_args(&[&command], &args) {
+        Ok(cli) => cli,
+        Err(argh::EarlyExit { output, status }) => match status {
+            Ok(()) => {
+                println!("{output}");
+                std::process::exit(0);
+            }
+            Err(()) => {
+                eprintln!("{output}\nRun {command} --help for more information.");
+                std::process::exit(1);
+            }
+        },
+    }
 }
 
 #[tokio::main]
 async fn main() -> ExitCode {
-    let Err(err) = fake_main().await else {
+    let cli = parse_args();
+    let output = cli.output.unwrap_or_default();
+    let json = cli.json;
+
+    let jsonl = (output == OutputFormat::Jsonl).then(|| Arc::new(JsonlReporter::default()));
+    let progress: Arc<dyn ProgressReporter> = match &jsonl {
+        Some(jsonl) => jsonl.clone(),
+        None => Arc::new(IndicatifReporter::new(MULTI_PROGRESS.clone())),
+    };
+
+    let Err(err) = fake_main(cli, progress).await else {
         return ExitCode::SUCCESS;
     };
 
-    let mut err: &dyn Error = &err;
-    let mut error_chain = vec![err];
+    let kind = err.kind();
+    let message = err.to_string();
 
-    while let Some(x) = err.source() {
-        error_chain.push(x);
-        err = x;
+    let mut source = err.source();
+    let mut chain = Vec::new();
+
+    while let Some(x) = source {
+        chain.push(x.to_string());
+        source = x.source();
     }
-    println!("Error: An error occured in `main`");
-    println!();
-    println!("Caused by:");
 
-    for (u, error) in error_chain.iter().enumerate() {
-        println!("{u:>4}: {error}");
+    // a failed upload was already reported as an error event of its own
+    if jsonl.is_some_and(|x| !x.has_failed()) {
+        let _ = Event::Error {
+            path: None,
+            kind: Some(kind),
+            message: &message,
+        }
+        .emit();
     }
 
-    ExitCode::FAILURE
+    if json {
+        let error = serde_json::json!({
+            "error": {
+                "kind": kind,
+                "message": message,
+                "chain": chain,
+            }
+        });
+        eprintln!("{error}");
+    } else {
+        eprintln!("Error: {message}");
+
+        if !chain.is_empty() {
+            eprintln!();
+            eprintln!("Caused by:");
+
+            for (u, error) in chain.iter().enumerate() {
+                eprintln!("{u:>4}: {error}");
+            }
+        }
+    }
+
+    ExitCode::from(exit_code(kind))
+}
+
+/// `Config::load`, except that settings which can't be parsed are reset to their defaults, telling
+/// which ones were.
+fn load_repairable_config() -> Result<Config, AppError> {
+    let (config, reset, error) = Config::load_salvaged()?;
+    if let Some(error) = error {
+        eprintln!(
+            "Warning: {error}, using the defaults for: {}",
+            reset.join(", ")
+        );
+    }
+    Ok(config)
+}
+
+/// Runs a `config` subcommand, without building the client.
+fn configure(command: ConfigSubCommands) -> Result<(), AppError> {
+    match command {
+        ConfigSubCommands::Save(SaveConfig { username, password }) => {
+            username_entry()?
+                .set_password(&username)
+                .map_err(AppError::FailureSettingVariable)?;
+            password_entry()?
+                .set_password(&password)
+                .map_err(AppError::FailureSettingVariable)?;
+        }
+        ConfigSubCommands::Set(SetConfig { key, value }) => {
+            let mut config = load_repairable_config()?;
+            config.set(&key, &value)?;
+            config.save()?;
+        }
+        ConfigSubCommands::Show(ShowConfig {}) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&load_repairable_config()?)
+                    .map_err(AppError::JsonTranslationError)?
+            );
+        }
+        ConfigSubCommands::Delete(DeleteConfig {}) => {
+            username_entry()?
+                .delete_credential()
+                .map_err(AppError::FailureSettingVariable)?;
+            password_entry()?
+                .delete_credential()
+                .map_err(AppError::FailureSettingVariable)?;
+        }
+    }
+    Ok(())
 }
 
 #[allow(clippy::too_many_lines)]
-async fn fake_main() -> Result<(), AppError> {
-    let cli: Cli = argh::from_env();
+async fn fake_main(cli: Cli, progress: Arc<dyn ProgressReporter>) -> Result<(), AppError> {
+    // completing only reads the listing cache, so the shell never waits on the config or keyring
+    if let CliSubCommands::Completions(CompletionsCommand {
+        shell,
+        complete: true,
+        words,
+    }) = &cli.command
+    {
+        let current = words.last().map_or("", String::as_str);
+        let candidates =
+            completions::candidates(&ListingCache::open()?, completions::complete(words))
+                .into_iter()
+                .filter(|x| x.value.starts_with(current))
+                .collect::<Vec<_>>();
+        print!("{}", shell.render(&candidates));
+        return Ok(());
+    }
+
+    // the config commands never touch the network, so a config that breaks it can be repaired
+    if let CliSubCommands::Config(ConfigCommand { command }) = cli.command {
+        return configure(command);
+    }
+
+    let config = Config::load()?;
+    let output = cli.output.unwrap_or_default();
+
+    let listing_ttl = if cli.refresh {
+        Duration::ZERO
+    } else {
+        config.listing_ttl.map_or(DEFAULT_TTL, Duration::from_secs)
+    };
+
+    let client = CatboxClient::builder()
+        .network(NetworkOptions {
+            proxy: cli.proxy.or_else(|| config.proxy.clone()),
+            ca_bundle: cli.ca_bundle.or_else(|| config.ca_bundle.clone()),
+            tls: cli.tls.unwrap_or(config.tls),
+        })
+        .endpoints(resolve_endpoints(cli.base_url, &config)?)
+        .keyring_credentials()
+        .progress(progress)
+        .listing_cache(ListingCache::open()?.with_ttl(listing_ttl))
+        .build()?;
 
     match cli.command {
         CliSubCommands::File(FileCommand {
@@ -180,89 +539,522 @@ async fn fake_main() -> Result<(), AppError> {
                     paths,
                     use_litterbox,
                     expiry,
+                    archive_format,
+                    exclude,
+                    recursive,
+                    album,
+                    strip_metadata,
+                    format,
+                    template,
+                    name,
                 }),
         }) => {
+            ensure!(
+                !(use_litterbox && album.is_some()),
+                AppError::AlbumWithLitterbox
+            );
+            let links = link_format(format, template)?;
+
+            let stdin = paths.iter().any(|x| x.as_os_str() == "-");
+            let paths = paths
+                .into_iter()
+                .filter(|x| x.as_os_str() != "-")
+                .collect::<Vec<_>>();
+
+            let options = UploadOptions {
+                strip_metadata: strip_metadata || config.strip_metadata,
+                archive: ArchiveOptions {
+                    format: archive_format.unwrap_or_default(),
+                    excludes: Excludes::new(exclude)?,
+                },
+            };
+
+            let paths = if recursive {
+                expand_directories(paths, &options.archive.excludes)?
+            } else {
+                paths
+            };
+            let stdin = if stdin {
+                Some(read_stdin(name).await?)
+            } else {
+                None
+            };
+
             if use_litterbox {
-                upload_temp_files(paths, expiry.unwrap_or(LitterExpiry::OneHour)).await?;
+                let expiry = expiry.unwrap_or(LitterExpiry::OneHour);
+                if let Some((name, bytes)) = stdin {
+                    let uploaded = client
+                        .upload_temp_data(name.clone(), bytes, &options, expiry)
+                        .await?;
+                    report_upload(Path::new(&name), &uploaded, output, links.as_ref())?;
+                }
+                upload_temp_files(&client, paths, &options, expiry, output, links.as_ref()).await?;
             } else {
-                upload_files(paths).await?;
+                let mut uploaded =
+                    upload_files(&client, paths, &options, output, links.as_ref()).await?;
+                if let Some((name, bytes)) = stdin {
+                    let data = client.upload_data(name.clone(), bytes, &options).await?;
+                    report_upload(Path::new(&name), &data, output, links.as_ref())?;
+                    uploaded.push(data);
+                }
+
+                if let Some(album) = album {
+                    let urls = uploaded.into_iter().map(|x| x.url).collect();
+                    add_to_album(&client, &album, urls).await?;
+                }
             }
         }
         CliSubCommands::File(FileCommand {
-            command: FileSubCommands::List(FileList {}),
+            command: FileSubCommands::List(list),
         }) => {
-            let user = USER_INSTANCE.get().await?;
-            let files = user.fetch_uploaded_files().await?;
+            let query = file_query(&list)?;
+            let links = link_format(list.format.clone(), list.template.clone())?;
+            if links.is_some() {
+                ensure!(
+                    !cli.json,
+                    AppError::ConflictingOptions {
+                        first: "json",
+                        second: "format",
+                    }
+                );
+                ensure!(
+                    !list.long,
+                    AppError::ConflictingOptions {
+                        first: "long",
+                        second: "format",
+                    }
+                );
+            }
+            let limit = list.limit.unwrap_or(usize::MAX);
+            // a single page, or the first files of a listing that isn't cached, are streamed
+            let whole = list.page.is_none()
+                && (list.limit.is_none() || query.reorders() || client.fresh_files().is_some());
+            let files = if whole {
+                let files = client.list_files().await?;
+                futures_util::stream::iter(files.into_iter().map(Ok)).left_stream()
+            } else {
+                client
+                    .files(list.page.map_or(1..=u32::MAX, |x| x..=x))
+                    .right_stream()
+            };
+
+            let mut files = if query.reorders() {
+                let mut files = query.apply(files.try_collect::<Vec<_>>().await?);
+                files.truncate(limit);
+                files
+            } else {
+                files
+                    .try_filter(|x| std::future::ready(query.filter.matches(x)))
+                    .take(limit)
+                    .try_collect::<Vec<_>>()
+                    .await?
+            };
 
             if cli.json {
-                println!("{}", serde_json::to_string_pretty(&files)?);
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&files).map_err(AppError::JsonTranslationError)?
+                );
+                return Ok(());
+            }
+
+            // text output is printed from the last file up, keep sorted files in sorted order
+            if query.sort.is_some() {
+                files.reverse();
+            }
+
+            if list.long {
+                print_file_table(&files);
+            } else if let Some(links) = links {
+                for x in files.iter().rev() {
+                    println!("{}", links.render(x));
+                }
             } else {
                 for (i, x) in files.into_iter().rev().enumerate() {
-                    println!("File {}: {x}", i + 1);
+                    println!("File {}: {}", i + 1, x.url);
                 }
             }
         }
         CliSubCommands::Album(AlbumCommand {
             command: AlbumSubCommands::Add(AddFiles { album, files }),
         }) => {
-            add_to_album(album, files).await?;
+            add_to_album(&client, &album, files).await?;
         }
         CliSubCommands::Album(AlbumCommand {
-            command: AlbumSubCommands::Upload(UploadFiles { album, files }),
+            command:
+                AlbumSubCommands::Upload(UploadFiles {
+                    album,
+                    files,
+                    strip_metadata,
+                    format,
+                    template,
+                }),
         }) => {
-            let urls = upload_files(files).await?;
+            let links = link_format(format, template)?;
+            let options = UploadOptions {
+                strip_metadata: strip_metadata || config.strip_metadata,
+                ..Default::default()
+            };
+            let uploaded = upload_files(&client, files, &options, output, links.as_ref()).await?;
 
-            add_to_album(album, urls).await?;
+            let urls = uploaded.into_iter().map(|x| x.url).collect();
+            add_to_album(&client, &album, urls).await?;
         }
         CliSubCommands::Album(AlbumCommand {
             command: AlbumSubCommands::List(AlbumList { album: Some(album) }),
         }) => {
-            let album = get_album(album)?;
-            let files = album.fetch_files().await?.urls;
+            let album = client.album(&album)?;
+            let (album, files) = client.fetch_album(&album).await?;
 
             if cli.json {
-                println!("{}", serde_json::to_string_pretty(&files)?);
+                let album = serde_json::json!({
+                    "album": album,
+                    "files": files.files,
+                });
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&album).map_err(AppError::JsonTranslationError)?
+                );
             } else {
-                for (i, x) in files.into_iter().rev().enumerate() {
+                if let Some(title) = &album.title {
+                    println!("Album: {title}");
+                }
+                if let Some(description) = album.description.as_ref().filter(|x| !x.is_empty()) {
+                    println!("{description}");
+                }
+                for (i, x) in files.urls().rev().enumerate() {
                     println!("File {}: {x}", i + 1);
                 }
             }
         }
         CliSubCommands::Album(AlbumCommand {
-            command: AlbumSubCommands::List(AlbumList { album: None }),
+            command:
+                AlbumSubCommands::Random(RandomFiles {
+                    album,
+                    count,
+                    ext,
+                    download,
+                    open,
+                    seed,
+                }),
+        }) => {
+            ensure!(
+                !(download && open),
+                AppError::ConflictingOptions {
+                    first: "download",
+                    second: "open",
+                }
+            );
+
+            let album = client.album(&album)?;
+            let files = client.album_files(&album).await?;
+
+            let filter = FileFilter {
+                extensions: ext,
+                ..Default::default()
+            };
+            let mut rng = seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
+            let picked = files.random_files(count, &filter, &mut rng);
+
+            if download {
+                for file in &picked {
+                    let path = PathBuf::from(file.slug());
+                    client.download(file.url.clone(), &path).await?;
+                    println!("{}", path.display());
+                }
+            } else if cli.json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&picked)
+                        .map_err(AppError::JsonTranslationError)?
+                );
+            } else {
+                for file in &picked {
+                    println!("{}", file.url);
+                }
+            }
+
+            if open {
+                for file in &picked {
+                    open::that_detached(file.url.as_str()).map_err(|source| AppError::OpenUrl {
+                        source,
+                        url: file.url.to_string(),
+                    })?;
+                }
+            }
+        }
+        CliSubCommands::Album(AlbumCommand {
+            command:
+                AlbumSubCommands::Sync(SyncAlbum {
+                    album,
+                    delete,
+                    dry_run,
+                    exclude,
+                    dir,
+                }),
         }) => {
-            let user = USER_INSTANCE.get().await?;
+            let album = client.album(&album)?;
+            let history = History::open()?;
+            let plan = client
+                .plan_sync(
+                    &album,
+                    &dir,
+                    &Excludes::new(exclude)?,
+                    delete,
+                    &history.entries()?,
+                )
+                .await?;
+
+            if dry_run {
+                if cli.json {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&plan)
+                            .map_err(AppError::JsonTranslationError)?
+                    );
+                } else {
+                    print_sync_plan(&plan);
+                }
+            } else {
+                // metadata is never stripped, so the uploads keep hashing like the local files
+                let options = UploadOptions::default();
+                let urls = futures_util::stream::iter(plan.uploads())
+                    .map(|x| {
+                        client
+                            .upload(x, &options)
+                            .map(move |y| Ok::<_, AppError>((x, y?)))
+                    })
+                    .buffer_unordered(5)
+                    .map(|x| {
+                        let (path, uploaded) = x?;
+                        // recorded right away, the next sync compares against the recorded hash
+                        history.append(&HistoryEntry::new(
+                            "sync",
+                            Some(path.to_path_buf()),
+                            &uploaded,
+                        ))?;
+                        report_upload(path, &uploaded, output, None)?;
+                        Ok::<_, AppError>(uploaded.url)
+                    })
+                    .try_collect()
+                    .await?;
+                add_to_album(&client, album.url.as_str(), urls).await?;
 
-            let albums = user.fetch_albums().await?;
+                let removals = plan.removals().map(UploadedFile::slug).collect::<Vec<_>>();
+                if !removals.is_empty() {
+                    let slugs = removals.iter().map(String::as_str).collect::<Vec<_>>();
+                    client.remove_from_album(&album, &slugs).await?;
+                    if output == OutputFormat::Text {
+                        for slug in &removals {
+                            println!("Removed {slug}");
+                        }
+                    }
+                }
+            }
+        }
+        CliSubCommands::Album(AlbumCommand {
+            command:
+                AlbumSubCommands::Pull(PullAlbum {
+                    album,
+                    delete,
+                    watch,
+                    dir,
+                }),
+        }) => {
+            let album = client.album(&album)?;
+
+            loop {
+                match client.pull_album(&album, &dir, delete).await {
+                    Ok(report) if cli.json => println!(
+                        "{}",
+                        serde_json::to_string_pretty(&report)
+                            .map_err(AppError::JsonTranslationError)?
+                    ),
+                    Ok(report) => {
+                        for path in &report.downloaded {
+                            println!("Downloaded {}", path.display());
+                        }
+                        for path in &report.removed {
+                            println!("Removed {}", path.display());
+                        }
+                    }
+                    // a failed poll shouldn't end the watch, the next one may succeed
+                    Err(error) if watch.is_some() => eprintln!("Error: {error}"),
+                    Err(error) => return Err(error.into()),
+                }
+
+                let Some(interval) = watch else {
+                    break;
+                };
+                tokio::time::sleep(interval).await;
+            }
+        }
+        CliSubCommands::Album(AlbumCommand {
+            command: AlbumSubCommands::Export(ExportAlbum { album, format, out }),
+        }) => {
+            let album = client.album(&album)?;
+            let (album, files) = client.fetch_album(&album).await?;
+            // only the account listing knows names and sizes, albums can be exported without one
+            let files = match client.list_files().await {
+                Ok(listing) => files.with_metadata(listing),
+                Err(
+                    UserError::LackOfCredentials
+                    | UserError::LackOfUser(_)
+                    | UserError::LackOfPassword(_)
+                    | UserError::KeyringInitilization(_),
+                ) => files,
+                Err(error) => return Err(error.into()),
+            };
+
+            let export = format
+                .render(&album, &files)
+                .map_err(AppError::JsonTranslationError)?;
+            match out {
+                Some(file) => std::fs::write(&file, export)
+                    .map_err(|source| AppError::WriteExport { source, file })?,
+                None => print!("{export}"),
+            }
+        }
+        CliSubCommands::Album(AlbumCommand {
+            command: AlbumSubCommands::List(AlbumList { album: None }),
+        }) => {
+            let albums = client.list_albums().await?;
 
             if cli.json {
-                let albums = albums.into_iter().map(|x| x.url).collect::<Vec<_>>();
-                println!("{}", serde_json::to_string_pretty(&albums)?);
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&albums)
+                        .map_err(AppError::JsonTranslationError)?
+                );
             } else {
-                for (i, x) in user.fetch_albums().await?.into_iter().rev().enumerate() {
-                    println!("Album {}: {}", i + 1, x.url);
+                for (i, x) in albums.into_iter().rev().enumerate() {
+                    match x.title.as_deref().filter(|x| !x.is_empty()) {
+                        Some(title) => println!("Album {}: {title} ({})", i + 1, x.url),
+                        None => println!("Album {}: {}", i + 1, x.url),
+                    }
                 }
             }
         }
-        CliSubCommands::Config(ConfigCommand {
-            command: ConfigSubCommands::Save(SaveConfig { username, password }),
+        CliSubCommands::Watch(WatchCommand {
+            dir,
+            recursive,
+            album,
+            use_litterbox,
+            expiry,
+            include,
+            exclude,
+            debounce,
+            strip_metadata,
         }) => {
-            get_username_entry()?
-                .set_password(&username)
-                .map_err(AppError::FailureSettingVariable)?;
-            get_password_entry()?
-                .set_password(&password)
-                .map_err(AppError::FailureSettingVariable)?;
+            ensure!(
+                !(use_litterbox && album.is_some()),
+                AppError::AlbumWithLitterbox
+            );
+
+            let destination = if use_litterbox {
+                Destination::Litterbox {
+                    expiry: expiry.unwrap_or(LitterExpiry::OneHour),
+                }
+            } else {
+                Destination::Catbox {
+                    album: album.map(|x| client.album(&x)).transpose()?,
+                }
+            };
+            let options = UploadOptions {
+                strip_metadata: strip_metadata || config.strip_metadata,
+                ..Default::default()
+            };
+            let rules = WatchRules::new(&include, &exclude, debounce, recursive)?;
+
+            watch::watch(
+                &client,
+                &dir,
+                &rules,
+                &destination,
+                &options,
+                &History::open()?,
+                output,
+            )
+            .await?;
         }
-        CliSubCommands::Config(ConfigCommand {
-            command: ConfigSubCommands::Delete(DeleteConfig {}),
+        CliSubCommands::Integrations(IntegrationsCommand {
+            command:
+                IntegrationsSubCommands::Sharex(ShareXUploader {
+                    use_litterbox,
+                    expiry,
+                    out,
+                }),
         }) => {
-            get_username_entry()?
-                .delete_credential()
-                .map_err(AppError::FailureSettingVariable)?;
-            get_password_entry()?
-                .delete_credential()
-                .map_err(AppError::FailureSettingVariable)?;
+            let target = if use_litterbox {
+                UploadTarget::Litterbox {
+                    expiry: expiry.unwrap_or(LitterExpiry::OneHour),
+                }
+            } else {
+                UploadTarget::Catbox {
+                    user_hash: client.user().await?.get_user_hash().await?,
+                }
+            };
+
+            let uploader =
+                serde_json::to_string_pretty(&sharex_uploader(client.endpoints(), &target))
+                    .map_err(AppError::JsonTranslationError)?;
+            match out {
+                Some(file) => std::fs::write(&file, uploader)
+                    .map_err(|source| AppError::WriteUploader { source, file })?,
+                None => println!("{uploader}"),
+            }
+        }
+        CliSubCommands::Integrations(IntegrationsCommand {
+            command:
+                IntegrationsSubCommands::Hook(ShellHook {
+                    tool,
+                    use_litterbox,
+                    expiry,
+                }),
+        }) => {
+            let expiry = use_litterbox.then(|| expiry.unwrap_or(LitterExpiry::OneHour));
+            print!("{}", hook_script(tool, expiry));
+        }
+        CliSubCommands::Serve(ServeCommand {
+            bind,
+            token,
+            token_file,
+            allow_delete,
+        }) => {
+            let mut tokens = token.into_iter().collect::<HashSet<_>>();
+            if let Some(file) = token_file {
+                let text = std::fs::read_to_string(&file)
+                    .map_err(|source| AppError::ReadTokens { source, file })?;
+                tokens.extend(
+                    text.lines()
+                        .map(|x| x.split('#').next().unwrap_or_default().trim())
+                        .filter(|x| !x.is_empty())
+                        .map(ToOwned::to_owned),
+                );
+            }
+
+            let gateway = Gateway {
+                client,
+                tokens,
+                history: History::open()?,
+                output,
+                allow_delete,
+            };
+            serve::serve(gateway, bind).await?;
+        }
+        CliSubCommands::Tui(TuiCommand { strip_metadata }) => {
+            let options = UploadOptions {
+                strip_metadata: strip_metadata || config.strip_metadata,
+                ..Default::default()
+            };
+            tui::run(&client, options).await?;
+        }
+        CliSubCommands::Completions(CompletionsCommand { shell, .. }) => {
+            print!("{}", shell.script());
+        }
+        CliSubCommands::Man(ManCommand {}) => print!("{}", man::page()),
+        CliSubCommands::Config(_) => {
+            unreachable!("config commands are run before the client is built")
         }
     }
 
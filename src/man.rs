@@ -0,0 +1,156 @@
+use std::fmt::Write;
+
+use argh::{ArgsInfo, CommandInfoWithArgs, FlagInfoKind, Optionality};
+
+use crate::{cli::Cli, completions::clean};
+
+/// Environment variables `cbx` reads, documented at the end of the page.
+const ENVIRONMENT: [(&str, &str); 8] = [
+    (
+        "CBX_USERNAME, CBX_PASSWORD",
+        "account to log in with, instead of the one saved in the keyring",
+    ),
+    (
+        "CBX_BASE_URL",
+        "site to talk to instead of catbox.moe, --base-url wins over it",
+    ),
+    (
+        "CBX_FILES_URL",
+        "where the files of the site are served from, instead of the one derived from the base url",
+    ),
+    (
+        "CBX_LITTERBOX_URL",
+        "Litterbox api to upload temporary files to, instead of the one derived from the base url",
+    ),
+    (
+        "CBX_CONFIG",
+        "config file to use instead of the default one",
+    ),
+    (
+        "CBX_HISTORY",
+        "upload history to use instead of the default one",
+    ),
+    (
+        "CBX_CACHE",
+        "listing cache to use instead of the default one",
+    ),
+    (
+        "HTTP_PROXY, HTTPS_PROXY, ALL_PROXY",
+        "proxy used when neither --proxy nor the config sets one",
+    ),
+];
+
+/// A roff man page(section 1) documenting every command of `cbx`.
+pub fn page() -> String {
+    let root = Cli::get_args_info();
+    let mut page = format!(
+        ".TH CBX 1 \"\" \"cbx {version}\" \"User Commands\"\n\
+         .SH NAME\n\
+         cbx \\- {description}\n\
+         .SH SYNOPSIS\n\
+         .B cbx\n\
+         [\\fIoptions\\fR] \\fIcommand\\fR [\\fIargs\\fR]\n\
+         .SH OPTIONS\n",
+        version = env!("CARGO_PKG_VERSION"),
+        description = roff(env!("CARGO_PKG_DESCRIPTION")),
+    );
+    arguments(&mut page, &root);
+
+    page.push_str(".SH COMMANDS\n");
+    for sub in &root.commands {
+        command(&mut page, &sub.command, &format!("cbx {}", sub.name));
+    }
+
+    page.push_str(".SH ENVIRONMENT\n");
+    for (name, description) in ENVIRONMENT {
+        let _ = writeln!(page, ".TP\n.B {name}\n{}", roff(description));
+    }
+    page
+}
+
+fn command(page: &mut String, info: &CommandInfoWithArgs, path: &str) {
+    let _ = writeln!(page, ".SS \"{}\"", roff(&synopsis(info, path)));
+    let _ = writeln!(page, "{}", text(info.description));
+    arguments(page, info);
+
+    for sub in &info.commands {
+        command(page, &sub.command, &format!("{path} {}", sub.name));
+    }
+}
+
+/// `path` followed by the required options and positionals of the command.
+fn synopsis(info: &CommandInfoWithArgs, path: &str) -> String {
+    let mut synopsis = path.to_owned();
+    for flag in info.flags.iter().filter(|x| !x.hidden) {
+        if let (Optionality::Required, FlagInfoKind::Option { arg_name }) =
+            (&flag.optionality, &flag.kind)
+        {
+            let _ = write!(synopsis, " {} <{arg_name}>", flag.long);
+        }
+    }
+    if info
+        .flags
+        .iter()
+        .any(|x| !x.hidden && x.long != "--help" && !matches!(x.optionality, Optionality::Required))
+    {
+        synopsis.push_str(" [options]");
+    }
+    if !info.commands.is_empty() {
+        synopsis.push_str(" <command>");
+    }
+    for positional in info.positionals.iter().filter(|x| !x.hidden) {
+        let _ = match positional.optionality {
+            Optionality::Required => write!(synopsis, " <{}>", positional.name),
+            Optionality::Optional => write!(synopsis, " [<{}>]", positional.name),
+            Optionality::Repeating | Optionality::Greedy => {
+                write!(synopsis, " [<{}>...]", positional.name)
+            }
+        };
+    }
+    synopsis
+}
+
+/// The positionals and flags of a command, `--help` is left out as every command has it.
+fn arguments(page: &mut String, info: &CommandInfoWithArgs) {
+    for positional in info.positionals.iter().filter(|x| !x.hidden) {
+        let _ = writeln!(
+            page,
+            ".TP\n\\fI{}\\fR\n{}",
+            positional.name,
+            text(positional.description)
+        );
+    }
+    for flag in info
+        .flags
+        .iter()
+        .filter(|x| !x.hidden && x.long != "--help")
+    {
+        let mut names = String::new();
+        if let Some(short) = flag.short {
+            let _ = write!(names, "\\fB\\-{short}\\fR, ");
+        }
+        let _ = write!(names, "\\fB{}\\fR", roff(flag.long));
+        if let FlagInfoKind::Option { arg_name } = flag.kind {
+            let _ = write!(names, " \\fI{arg_name}\\fR");
+        }
+        let _ = writeln!(page, ".TP\n{names}\n{}", text(flag.description));
+    }
+}
+
+/// A description as roff, with its values and default on lines of their own.
+fn text(description: &str) -> String {
+    ["Supported Values:", "If not provided"]
+        .iter()
+        .fold(roff(&clean(description)), |text, line| {
+            text.replace(&format!(" {line}"), &format!("\n.br\n{line}"))
+        })
+}
+
+/// Escapes `text` so roff prints it as it is.
+fn roff(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    match text.chars().next() {
+        Some('.' | '\'') => format!("\\&{text}"),
+        _ => text,
+    }
+}
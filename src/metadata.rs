@@ -0,0 +1,232 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl ImageFormat {
+    /// Detects the format from the magic bytes, returns `None` for anything that is not supported.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&[0xFF, 0xD8]) {
+            Some(Self::Jpeg)
+        } else if bytes.starts_with(PNG_SIGNATURE) {
+            Some(Self::Png)
+        } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Some(Self::Webp)
+        } else {
+            None
+        }
+    }
+
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Png => "png",
+            Self::Webp => "webp",
+        }
+    }
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Strips privacy sensitive metadata(EXIF, XMP, ICC profiles, text chunks) of a supported image.
+///
+/// Only the container is rewritten, pixel data is copied verbatim.
+/// Returns `Ok(None)` when the bytes are not a supported image, and `Err` with a reason when the
+/// image is malformed.
+pub fn strip_metadata(bytes: &[u8]) -> Result<Option<Vec<u8>>, &'static str> {
+    match ImageFormat::detect(bytes) {
+        Some(ImageFormat::Jpeg) => strip_jpeg(bytes).map(Some),
+        Some(ImageFormat::Png) => strip_png(bytes).map(Some),
+        Some(ImageFormat::Webp) => strip_webp(bytes).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn strip_jpeg(bytes: &[u8]) -> Result<Vec<u8>, &'static str> {
+    // APP1(EXIF/XMP), APP2(ICC), APP13(IPTC) and comments
+    const STRIPPED: [u8; 4] = [0xE1, 0xE2, 0xED, 0xFE];
+
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(&bytes[..2]);
+
+    let mut pos = 2;
+    while pos < bytes.len() {
+        if bytes[pos] != 0xFF {
+            return Err("expected a jpeg marker");
+        }
+        // markers may be padded with any amount of 0xFF
+        while bytes.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+        let marker = *bytes.get(pos + 1).ok_or("truncated jpeg marker")?;
+
+        match marker {
+            // start of scan, everything after it is entropy coded image data
+            0xDA => {
+                out.extend_from_slice(&bytes[pos..]);
+                return Ok(out);
+            }
+            0xD9 => {
+                out.extend_from_slice(&bytes[pos..pos + 2]);
+                return Ok(out);
+            }
+            0x01 | 0xD0..=0xD7 => {
+                out.extend_from_slice(&bytes[pos..pos + 2]);
+                pos += 2;
+            }
+            _ => {
+                let len = bytes
+                    .get(pos + 2..pos + 4)
+                    .map(|x| usize::from(u16::from_be_bytes([x[0], x[1]])))
+                    .ok_or("truncated jpeg segment")?;
+                let end = pos + 2 + len;
+                if len < 2 || end > bytes.len() {
+                    return Err("jpeg segment exceeds file");
+                }
+                if !STRIPPED.contains(&marker) {
+                    out.extend_from_slice(&bytes[pos..end]);
+                }
+                pos = end;
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn strip_png(bytes: &[u8]) -> Result<Vec<u8>, &'static str> {
+    const STRIPPED: [&[u8; 4]; 6] = [b"eXIf", b"iCCP", b"tEXt", b"zTXt", b"iTXt", b"tIME"];
+
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(PNG_SIGNATURE);
+
+    let mut pos = PNG_SIGNATURE.len();
+    while pos < bytes.len() {
+        let header = bytes.get(pos..pos + 8).ok_or("truncated png chunk")?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let kind = &header[4..8];
+        // length + type + data + crc
+        let end = pos + 12 + len;
+        if end > bytes.len() {
+            return Err("png chunk exceeds file");
+        }
+        if !STRIPPED.iter().any(|x| x.as_slice() == kind) {
+            out.extend_from_slice(&bytes[pos..end]);
+        }
+        pos = end;
+        if kind == b"IEND" {
+            break;
+        }
+    }
+    Ok(out)
+}
+
+fn strip_webp(bytes: &[u8]) -> Result<Vec<u8>, &'static str> {
+    const STRIPPED: [&[u8; 4]; 3] = [b"EXIF", b"XMP ", b"ICCP"];
+    // flags of the VP8X header that announce the stripped chunks
+    const ICC_FLAG: u8 = 0x20;
+    const EXIF_FLAG: u8 = 0x08;
+    const XMP_FLAG: u8 = 0x04;
+
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(&bytes[..12]);
+
+    let mut pos = 12;
+    while pos < bytes.len() {
+        let header = bytes.get(pos..pos + 8).ok_or("truncated webp chunk")?;
+        let kind = &header[..4];
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        // chunks are padded to an even size
+        let end = (pos + 8 + len + (len & 1)).min(bytes.len());
+        if pos + 8 + len > bytes.len() {
+            return Err("webp chunk exceeds file");
+        }
+        if !STRIPPED.iter().any(|x| x.as_slice() == kind) {
+            let start = out.len();
+            out.extend_from_slice(&bytes[pos..end]);
+            if kind == b"VP8X" && len > 0 {
+                out[start + 8] &= !(ICC_FLAG | EXIF_FLAG | XMP_FLAG);
+            }
+        }
+        pos = end;
+    }
+
+    let riff_size = u32::try_from(out.len() - 8).map_err(|_| "webp file too large")?;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(marker: u8, data: &[u8]) -> Vec<u8> {
+        let len = u16::try_from(data.len() + 2).unwrap();
+        [&[0xFF, marker], &len.to_be_bytes()[..], data].concat()
+    }
+
+    fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let len = u32::try_from(data.len()).unwrap();
+        let padding: &[u8] = if data.len() % 2 == 1 { &[0] } else { &[] };
+        [&kind[..], &len.to_le_bytes(), data, padding].concat()
+    }
+
+    fn riff(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body = [&b"WEBP"[..], &chunks.concat()].concat();
+        let len = u32::try_from(body.len()).unwrap();
+        [&b"RIFF"[..], &len.to_le_bytes(), &body].concat()
+    }
+
+    #[test]
+    fn jpeg_loses_app1_and_app2() {
+        let jfif = segment(0xE0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+        let quantization = segment(0xDB, &[0; 65]);
+        let scan = [&segment(0xDA, &[1, 2, 3])[..], &[0x12, 0x34, 0xFF, 0xD9]].concat();
+        let image = [
+            &[0xFF, 0xD8][..],
+            &jfif,
+            &segment(0xE1, b"Exif\0\0MM\0*"),
+            &segment(0xE1, b"http://ns.adobe.com/xap/1.0/\0<x:xmpmeta/>"),
+            &segment(0xE2, b"ICC_PROFILE\0\x01\x01"),
+            &quantization,
+            &scan,
+        ]
+        .concat();
+
+        let stripped = strip_metadata(&image).unwrap().unwrap();
+        assert_eq!(
+            stripped,
+            [&[0xFF, 0xD8][..], &jfif, &quantization, &scan].concat()
+        );
+    }
+
+    #[test]
+    fn truncated_jpeg_is_malformed() {
+        let image = [&[0xFF, 0xD8][..], &segment(0xE1, b"Exif\0\0")[..6]].concat();
+        assert!(strip_metadata(&image).is_err());
+    }
+
+    #[test]
+    fn webp_loses_exif_xmp_and_iccp() {
+        // VP8X announcing an ICC profile, EXIF and XMP
+        let header = |flags: u8| chunk(b"VP8X", &[flags, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let pixels = chunk(b"VP8L", &[0x2F, 0, 0, 0, 0]);
+        let image = riff(&[
+            header(0x2C | 0x10),
+            chunk(b"ICCP", b"odd"),
+            pixels.clone(),
+            chunk(b"EXIF", b"MM\0*"),
+            chunk(b"XMP ", b"<x:xmpmeta/>"),
+        ]);
+
+        let stripped = strip_metadata(&image).unwrap().unwrap();
+        // only the alpha flag is left
+        assert_eq!(stripped, riff(&[header(0x10), pixels]));
+    }
+
+    #[test]
+    fn other_files_are_left_alone() {
+        assert_eq!(strip_metadata(b"GIF89a"), Ok(None));
+    }
+}
@@ -1,14 +1,78 @@
 use super::errors::*;
-use reqwest::{Client, ClientBuilder};
-use std::sync::Arc;
+use reqwest::{Certificate, Client, ClientBuilder, Proxy};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 
 use reqwest::{
     cookie::{self},
     header,
 };
 
-pub fn create_spoof_client(
-    cookie_provider: impl Into<Option<Arc<cookie::Jar>>>,
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsBackend {
+    #[default]
+    Native,
+    Rustls,
+}
+
+impl FromStr for TlsBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "native" => Ok(Self::Native),
+            "rustls" => Ok(Self::Rustls),
+            s => Err(format!(
+                "invalid tls backend `{s}` (expected one of: native, rustls)"
+            )),
+        }
+    }
+}
+
+/// Connection settings of the HTTP client.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkOptions {
+    /// `http://`, `https://` or `socks5://` proxy used for every request.
+    ///
+    /// `None` honours the `HTTP_PROXY`/`HTTPS_PROXY`/`ALL_PROXY`/`NO_PROXY` environment variables,
+    /// while `"none"` disables proxies entirely.
+    pub proxy: Option<String>,
+    /// PEM file with extra root certificates to trust
+    pub ca_bundle: Option<PathBuf>,
+    pub tls: TlsBackend,
+}
+
+/// Parses a proxy url the way `NetworkOptions::proxy` is used, `none` aside.
+pub fn parse_proxy(proxy: &str) -> Result<Proxy, NetworkError> {
+    Proxy::all(proxy).map_err(|source| NetworkError::InvalidProxy {
+        source,
+        proxy: proxy.to_owned(),
+    })
+}
+
+/// Reads the certificates of a PEM bundle.
+pub fn read_ca_bundle(path: &Path) -> Result<Vec<Certificate>, NetworkError> {
+    let pem = std::fs::read(path).map_err(|source| NetworkError::ReadCaBundle {
+        source,
+        path: path.to_path_buf(),
+    })?;
+    Certificate::from_pem_bundle(&pem).map_err(|source| NetworkError::InvalidCaBundle {
+        source,
+        path: path.to_path_buf(),
+    })
+}
+
+pub(crate) fn create_spoof_client(
+    options: &NetworkOptions,
+    cookie_provider: Arc<cookie::Jar>,
 ) -> Result<Client, NetworkError> {
     let headers = header::HeaderMap::from_iter([
             (
@@ -28,16 +92,28 @@ pub fn create_spoof_client(
                 header::HeaderValue::from_static("en-US,en;q=0.9"),
             )
         ]);
-    let mut builder = ClientBuilder::new();
 
-    if let Some(provider) = cookie_provider.into() {
-        builder = builder.cookie_provider(provider);
+    let mut builder = ClientBuilder::new()
+        .default_headers(headers)
+        .cookie_provider(cookie_provider)
+        .connect_timeout(CONNECT_TIMEOUT);
+
+    match options.proxy.as_deref() {
+        None => {}
+        Some("none") => builder = builder.no_proxy(),
+        Some(proxy) => builder = builder.proxy(parse_proxy(proxy)?),
     }
 
-    builder
-        .no_proxy()
-        .default_headers(headers)
-        .cookie_store(true)
-        .build()
-        .map_err(NetworkError::ClientCreation)
+    if let Some(path) = &options.ca_bundle {
+        for certificate in read_ca_bundle(path)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    builder = match options.tls {
+        TlsBackend::Native => builder.use_native_tls(),
+        TlsBackend::Rustls => builder.use_rustls_tls(),
+    };
+
+    builder.build().map_err(NetworkError::ClientCreation)
 }
@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use tokio::sync::mpsc;
+
+/// Identifies a single operation reported to a `ProgressReporter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TaskId(u64);
+
+impl TaskId {
+    fn next() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        Self(COUNTER.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// What a task transfers, so reporters can tell uploads from downloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TaskKind {
+    /// sends a file to the host, `bytes_sent` counts the bytes read from it
+    Upload,
+    /// writes a file of the host to disk
+    Download,
+    /// anything else, e.g. logging in or fetching pages
+    Other,
+}
+
+/// Receives the progress of every operation.
+///
+/// Each task is `started` once, then either `finished` or `failed` once.
+/// `bytes_sent` is only reported for tasks started with a `total`.
+pub trait ProgressReporter: Send + Sync {
+    /// `total` is the amount of bytes the task will send, `None` when the size isn't known.
+    fn started(&self, task: TaskId, kind: TaskKind, label: &str, total: Option<u64>);
+    fn bytes_sent(&self, task: TaskId, bytes: u64);
+    fn finished(&self, task: TaskId);
+    fn failed(&self, task: TaskId, error: &str);
+}
+
+/// Reports nothing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopReporter;
+
+impl ProgressReporter for NoopReporter {
+    fn started(&self, _: TaskId, _: TaskKind, _: &str, _: Option<u64>) {}
+    fn bytes_sent(&self, _: TaskId, _: u64) {}
+    fn finished(&self, _: TaskId) {}
+    fn failed(&self, _: TaskId, _: &str) {}
+}
+
+/// Draws a progress bar per task, or a spinner for tasks without a known size.
+#[derive(Debug, Default)]
+pub struct IndicatifReporter {
+    multi: MultiProgress,
+    bars: Mutex<HashMap<TaskId, ProgressBar>>,
+}
+
+impl IndicatifReporter {
+    pub fn new(multi: MultiProgress) -> Self {
+        Self {
+            multi,
+            bars: Mutex::default(),
+        }
+    }
+
+    fn take(&self, task: TaskId) -> Option<ProgressBar> {
+        self.bars
+            .lock()
+            .expect("progress bars poisoned")
+            .remove(&task)
+    }
+}
+
+impl ProgressReporter for IndicatifReporter {
+    /// # Panics
+    ///
+    /// Panics when the template provided to `ProgressBar` is invalid(compile time mistake)
+    fn started(&self, task: TaskId, _: TaskKind, label: &str, total: Option<u64>) {
+        let bar = match total {
+            Some(total) => {
+                let bar = ProgressBar::new(total).with_prefix(label.to_owned());
+                bar.set_style(
+                    #[allow(clippy::literal_string_with_formatting_args)]
+                    ProgressStyle::with_template(
+                        "{prefix:.magenta}\n[ETA: {eta}] [{decimal_bytes_per_sec:}] [{elapsed_precise}] {wide_bar:.cyan/blue} {decimal_bytes}/{decimal_total_bytes}",
+                    )
+                    .expect("Invalid template(compile time issue)")
+                    .progress_chars("##-"),
+                );
+                bar.enable_steady_tick(Duration::from_millis(500));
+                bar
+            }
+            None => {
+                let bar = ProgressBar::new_spinner().with_message(label.to_owned());
+                bar.enable_steady_tick(Duration::from_millis(100));
+                bar
+            }
+        };
+
+        let bar = self.multi.add(bar);
+        self.bars
+            .lock()
+            .expect("progress bars poisoned")
+            .insert(task, bar);
+    }
+
+    fn bytes_sent(&self, task: TaskId, bytes: u64) {
+        if let Some(bar) = self.bars.lock().expect("progress bars poisoned").get(&task) {
+            bar.inc(bytes);
+        }
+    }
+
+    fn finished(&self, task: TaskId) {
+        if let Some(bar) = self.take(task) {
+            bar.finish_and_clear();
+        }
+    }
+
+    fn failed(&self, task: TaskId, _: &str) {
+        // the error itself is reported by whoever awaits the operation
+        if let Some(bar) = self.take(task) {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    Started {
+        task: TaskId,
+        kind: TaskKind,
+        label: String,
+        total: Option<u64>,
+    },
+    BytesSent {
+        task: TaskId,
+        bytes: u64,
+    },
+    Finished {
+        task: TaskId,
+    },
+    Failed {
+        task: TaskId,
+        error: String,
+    },
+}
+
+/// Forwards every report as a `ProgressEvent`, for frontends that render progress themselves.
+///
+/// # Example
+///
+/// ```
+/// let (reporter, mut events) = ChannelReporter::new();
+/// let client = client.with_progress(Arc::new(reporter));
+/// while let Some(event) = events.recv().await { /* draw */ }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChannelReporter {
+    tx: mpsc::UnboundedSender<ProgressEvent>,
+}
+
+impl ChannelReporter {
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<ProgressEvent>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self { tx }, rx)
+    }
+
+    fn send(&self, event: ProgressEvent) {
+        // nobody listening anymore is not an error of the operation
+        let _ = self.tx.send(event);
+    }
+}
+
+impl ProgressReporter for ChannelReporter {
+    fn started(&self, task: TaskId, kind: TaskKind, label: &str, total: Option<u64>) {
+        self.send(ProgressEvent::Started {
+            task,
+            kind,
+            label: label.to_owned(),
+            total,
+        });
+    }
+
+    fn bytes_sent(&self, task: TaskId, bytes: u64) {
+        self.send(ProgressEvent::BytesSent { task, bytes });
+    }
+
+    fn finished(&self, task: TaskId) {
+        self.send(ProgressEvent::Finished { task });
+    }
+
+    fn failed(&self, task: TaskId, error: &str) {
+        self.send(ProgressEvent::Failed {
+            task,
+            error: error.to_owned(),
+        });
+    }
+}
+
+/// A started task, reporting to the reporter it was started on.
+#[derive(Clone)]
+pub(crate) struct Task {
+    reporter: Arc<dyn ProgressReporter>,
+    id: TaskId,
+}
+
+impl Task {
+    pub(crate) fn start(
+        reporter: &Arc<dyn ProgressReporter>,
+        kind: TaskKind,
+        label: impl Display,
+        total: Option<u64>,
+    ) -> Self {
+        let id = TaskId::next();
+        reporter.started(id, kind, &label.to_string(), total);
+        Self {
+            reporter: reporter.clone(),
+            id,
+        }
+    }
+
+    pub(crate) fn advance(&self, bytes: u64) {
+        self.reporter.bytes_sent(self.id, bytes);
+    }
+
+    /// Reports the outcome of the task, passing `result` through.
+    pub(crate) fn complete<T, E: Display>(self, result: Result<T, E>) -> Result<T, E> {
+        match &result {
+            Ok(_) => self.reporter.finished(self.id),
+            Err(err) => self.reporter.failed(self.id, &err.to_string()),
+        }
+        result
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    path::Path,
+    sync::Arc,
+};
+
+use axum::{
+    extract::{multipart::Field, DefaultBodyLimit, FromRequest, Multipart, Request, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Form, Router,
+};
+use catbox::{
+    ensure,
+    history::{History, HistoryEntry},
+    AppError, CatboxClient, UserError,
+};
+use futures_util::stream;
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use tokio::{net::TcpListener, sync::mpsc};
+
+use crate::{events::OutputFormat, report_upload, MULTI_PROGRESS};
+
+/// Requests forwarded as they are, with the userhash of the account swapped in.
+const FORWARDED: [&str; 5] = [
+    "createalbum",
+    "editalbum",
+    "addtoalbum",
+    "removefromalbum",
+    "deletealbum",
+];
+
+/// How many chunks of an upload may be waiting for catbox before the client is slowed down.
+const CHUNK_BACKLOG: usize = 4;
+
+/// Everything shared by the requests of `cbx serve`.
+pub struct Gateway {
+    pub client: CatboxClient,
+    /// every request is accepted when empty, which is only allowed on loopback addresses
+    pub tokens: HashSet<String>,
+    pub history: History,
+    pub output: OutputFormat,
+    /// forward `deletefiles` too, letting every client delete any file of the account
+    pub allow_delete: bool,
+}
+
+/// Serves a catbox compatible `api.php` on `address` until the process is interrupted.
+///
+/// Clients authenticate by sending one of `tokens` as their userhash, or as a bearer token.
+/// Without tokens only loopback addresses are served, as anyone reaching it could upload.
+pub async fn serve(gateway: Gateway, address: SocketAddr) -> Result<(), AppError> {
+    ensure!(
+        !gateway.tokens.is_empty() || address.ip().is_loopback(),
+        AppError::UnprotectedGateway { address }
+    );
+
+    let bind_error = |source| AppError::BindGateway { source, address };
+    let listener = TcpListener::bind(address).await.map_err(bind_error)?;
+    let local = listener.local_addr().map_err(bind_error)?;
+    eprintln!("Listening on http://{local}/user/api.php");
+
+    let app = Router::new()
+        .route("/user/api.php", post(api))
+        .route("/api.php", post(api))
+        .layer(DefaultBodyLimit::disable())
+        .with_state(Arc::new(gateway));
+
+    axum::serve(listener, app)
+        .await
+        .map_err(AppError::ServeGateway)
+}
+
+async fn api(State(gateway): State<Arc<Gateway>>, request: Request) -> Response {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.strip_prefix("Bearer "))
+        .map(ToOwned::to_owned);
+    let is_multipart = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok())
+        .is_some_and(|x| x.starts_with("multipart/form-data"));
+
+    let fields = if is_multipart {
+        let mut multipart = match Multipart::from_request(request, &()).await {
+            Ok(x) => x,
+            Err(rejection) => return rejection.into_response(),
+        };
+
+        let mut fields = HashMap::new();
+        loop {
+            let field = match multipart.next_field().await {
+                Ok(Some(x)) => x,
+                Ok(None) => break,
+                Err(error) => return error.into_response(),
+            };
+            let name = field.name().unwrap_or_default().to_owned();
+
+            // the file is passed on while it is received, so it has to come after the other fields
+            if name == "fileToUpload" {
+                if !gateway.allows(token(bearer.as_deref(), &fields)) {
+                    return unauthorized();
+                }
+                if fields.get("reqtype").is_some_and(|x| x != "fileupload") {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        "Files can only be sent with fileupload.",
+                    )
+                        .into_response();
+                }
+                return gateway.upload(field).await;
+            }
+
+            match field.text().await {
+                Ok(text) => {
+                    fields.insert(name, text);
+                }
+                Err(error) => return error.into_response(),
+            }
+        }
+        fields
+    } else {
+        match Form::<HashMap<String, String>>::from_request(request, &()).await {
+            Ok(Form(x)) => x,
+            Err(rejection) => return rejection.into_response(),
+        }
+    };
+
+    if !gateway.allows(token(bearer.as_deref(), &fields)) {
+        return unauthorized();
+    }
+    gateway.forward(&fields).await
+}
+
+/// The bearer token, or else the userhash catbox clients send.
+fn token<'a>(bearer: Option<&'a str>, fields: &'a HashMap<String, String>) -> Option<&'a str> {
+    bearer.or(fields.get("userhash").map(String::as_str))
+}
+
+/// Compares `a` and `b` in a time that depends on neither, by comparing their fixed-size digests.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    Sha256::digest(a)
+        .iter()
+        .zip(Sha256::digest(b).iter())
+        .fold(0, |difference, (a, b)| difference | (a ^ b))
+        == 0
+}
+
+fn unauthorized() -> Response {
+    (StatusCode::UNAUTHORIZED, "Invalid token.").into_response()
+}
+
+impl Gateway {
+    /// Whether `token` is one of the tokens, every token is compared so the time taken doesn't
+    /// tell which one came close.
+    fn allows(&self, token: Option<&str>) -> bool {
+        self.tokens.is_empty()
+            || token.is_some_and(|token| {
+                self.tokens
+                    .iter()
+                    .fold(false, |found, x| found | constant_time_eq(token, x))
+            })
+    }
+
+    /// Streams `field` to the account, chunk by chunk as the client sends it.
+    async fn upload(&self, mut field: Field<'_>) -> Response {
+        let file_name = field
+            .file_name()
+            .filter(|x| !x.is_empty())
+            .unwrap_or("upload")
+            .to_owned();
+
+        let (sender, receiver) = mpsc::channel(CHUNK_BACKLOG);
+        let body = stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|x| (x, receiver))
+        });
+        let receive = async move {
+            loop {
+                let chunk = match field.chunk().await {
+                    Ok(Some(x)) => Ok(x),
+                    Ok(None) => break,
+                    Err(error) => Err(std::io::Error::other(error)),
+                };
+                let failed = chunk.is_err();
+                // a closed channel means the upload already failed
+                if sender.send(chunk).await.is_err() || failed {
+                    break;
+                }
+            }
+        };
+
+        let (uploaded, ()) =
+            tokio::join!(self.client.upload_stream(file_name.clone(), body), receive);
+        let uploaded = match uploaded {
+            Ok(x) => x,
+            Err(error) => return failure(error),
+        };
+
+        if let Err(error) = report_upload(Path::new(&file_name), &uploaded, self.output, None) {
+            eprintln!("Error: {error}");
+        }
+        self.record(&HistoryEntry::new("serve", None, &uploaded));
+        uploaded.url.into_response()
+    }
+
+    /// Sends a request without a file to the account.
+    async fn forward(&self, fields: &HashMap<String, String>) -> Response {
+        let reqtype = fields
+            .get("reqtype")
+            .map(String::as_str)
+            .unwrap_or_default();
+
+        let response = match reqtype {
+            "fileupload" => return (StatusCode::BAD_REQUEST, "No files given.").into_response(),
+            "urlupload" => {
+                let Some(url) = fields.get("url").and_then(|x| Url::parse(x).ok()) else {
+                    return (StatusCode::BAD_REQUEST, "Invalid url.").into_response();
+                };
+                self.client.upload_url(&url).await.inspect(|uploaded| {
+                    if self.output == OutputFormat::Text {
+                        let _ = MULTI_PROGRESS.println(format!("{url}: {}", uploaded.trim()));
+                    }
+                    self.record(&HistoryEntry::remote("serve", uploaded.trim().to_owned()));
+                })
+            }
+            x if FORWARDED.contains(&x) || x == "deletefiles" && self.allow_delete => {
+                let form = fields
+                    .iter()
+                    .filter(|(key, _)| *key != "userhash")
+                    .map(|(key, value)| (key.as_str(), value.as_str()))
+                    .collect::<Vec<_>>();
+                let response = match self.client.user().await {
+                    Ok(user) => user.api_request(&form).await,
+                    Err(error) => Err(error),
+                };
+                self.client.invalidate_listings();
+                response
+            }
+            x => return (StatusCode::BAD_REQUEST, format!("Unknown reqtype {x}.")).into_response(),
+        };
+
+        match response {
+            Ok(text) => text.into_response(),
+            Err(error) => failure(error),
+        }
+    }
+
+    fn record(&self, entry: &HistoryEntry) {
+        if let Err(error) = self.history.append(entry) {
+            eprintln!("Error: {error}");
+        }
+    }
+}
+
+/// Passes errors of catbox on to the client, anything else is the gateway's fault.
+fn failure(error: UserError) -> Response {
+    eprintln!("Error: {error}");
+    match error {
+        UserError::InvalidResponseWithCode { code, reason }
+        | UserError::AlbumRequest { code, reason }
+        | UserError::ApiRequest { code, reason } => (code, reason).into_response(),
+        error => (StatusCode::BAD_GATEWAY, error.to_string()).into_response(),
+    }
+}
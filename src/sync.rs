@@ -0,0 +1,305 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io::ErrorKind as IoErrorKind,
+    path::{Path, PathBuf},
+};
+
+use futures_util::TryStreamExt;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs::{self, File};
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    album::Album,
+    archive::{walk_files, Excludes},
+    ensure,
+    errors::*,
+    file::UploadedFile,
+    history::HistoryEntry,
+    upload::to_hex,
+    CatboxClient,
+};
+
+/// One step of bringing an album in line with a local directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SyncAction {
+    /// no album file was uploaded under this file's name
+    Upload { path: PathBuf },
+    /// the album file uploaded under this file's name has different contents
+    Replace {
+        path: PathBuf,
+        previous: UploadedFile,
+    },
+    /// the album file's name no longer exists locally
+    Remove { file: UploadedFile },
+}
+
+/// Everything `sync::plan` found, nothing is changed until the plan is carried out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SyncPlan {
+    pub actions: Vec<SyncAction>,
+    /// local files already in the album with the same contents
+    pub unchanged: Vec<PathBuf>,
+}
+
+impl SyncPlan {
+    /// Local files that have to be uploaded and added to the album.
+    pub fn uploads(&self) -> impl Iterator<Item = &Path> {
+        self.actions.iter().filter_map(|x| match x {
+            SyncAction::Upload { path } | SyncAction::Replace { path, .. } => Some(path.as_path()),
+            SyncAction::Remove { .. } => None,
+        })
+    }
+
+    /// Album files that have to be removed from the album, replaced ones included, each once.
+    pub fn removals(&self) -> impl Iterator<Item = &UploadedFile> {
+        let mut seen = HashSet::new();
+        self.actions
+            .iter()
+            .filter_map(|x| match x {
+                SyncAction::Replace { previous: file, .. } | SyncAction::Remove { file } => {
+                    Some(file)
+                }
+                SyncAction::Upload { .. } => None,
+            })
+            .filter(move |x| seen.insert(x.slug()))
+    }
+}
+
+/// Compares every file below `dir` with the files of `album`.
+///
+/// Album files are matched to local files by the name they were uploaded under, which only the
+/// account listing knows, so files of other accounts are never touched. Matches are compared by
+/// SHA-256, taken from the `history` entry of the album file when it has one and otherwise hashed
+/// while streaming the album file. Local files sharing a name in different subdirectories are
+/// compared against the same album files.
+///
+/// The state files of `sync::pull` are never uploaded.
+///
+/// With `delete`, album files whose name no longer exists locally are planned for removal.
+pub async fn plan(
+    client: &CatboxClient,
+    album: &Album,
+    dir: &Path,
+    excludes: &Excludes,
+    delete: bool,
+    history: &[HistoryEntry],
+) -> Result<SyncPlan, UserError> {
+    let mut local = walk_files(dir, excludes)?;
+    local.retain(|x| x.file_name() != Some(PULL_STATE.as_ref()));
+
+    let album_files = client.album_files(album).await?;
+    let mut names = client
+        .list_files()
+        .await?
+        .into_iter()
+        .map(|x| (x.slug(), x))
+        .collect::<HashMap<_, _>>();
+    let album_files = album_files
+        .files
+        .iter()
+        .filter_map(|x| names.remove(&x.slug()))
+        .filter(|x| x.name.is_some())
+        .collect::<Vec<_>>();
+
+    let mut plan = SyncPlan::default();
+    let mut remote_hashes = history
+        .iter()
+        .filter_map(|x| Some((client.file_slug(&x.url), x.sha256.clone()?)))
+        .collect::<HashMap<_, _>>();
+
+    for relative in &local {
+        let path = dir.join(relative);
+        let name = relative
+            .file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let candidates = album_files
+            .iter()
+            .filter(|x| x.name.as_deref() == Some(name.as_str()))
+            .collect::<Vec<_>>();
+
+        let Some(previous) = candidates.first() else {
+            plan.actions.push(SyncAction::Upload { path });
+            continue;
+        };
+
+        let sha256 = hash_file(&path).await?;
+        let mut unchanged = false;
+        for candidate in &candidates {
+            let slug = candidate.slug();
+            if !remote_hashes.contains_key(&slug) {
+                let hash = client.sha256(candidate.url.clone()).await?;
+                remote_hashes.insert(slug.clone(), hash);
+            }
+            if remote_hashes[&slug] == sha256 {
+                unchanged = true;
+                break;
+            }
+        }
+
+        if unchanged {
+            plan.unchanged.push(path);
+        } else {
+            plan.actions.push(SyncAction::Replace {
+                path,
+                previous: (*previous).clone(),
+            });
+        }
+    }
+
+    if delete {
+        let local_names = local
+            .iter()
+            .filter_map(|x| x.file_name())
+            .map(|x| x.to_string_lossy())
+            .collect::<HashSet<_>>();
+        plan.actions.extend(
+            album_files
+                .iter()
+                .filter(|x| x.name.as_deref().is_some_and(|x| !local_names.contains(x)))
+                .map(|x| SyncAction::Remove { file: x.clone() }),
+        );
+    }
+
+    Ok(plan)
+}
+
+/// Name of the file `pull` remembers pulled files in, inside the pulled directory.
+pub const PULL_STATE: &str = ".cbx-pull.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PullState {
+    album: Url,
+    /// slugs downloaded by earlier pulls
+    files: BTreeSet<String>,
+}
+
+/// What one `sync::pull` changed in the directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PullReport {
+    pub downloaded: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    /// files already pulled, which weren't fetched again
+    pub unchanged: usize,
+}
+
+/// Downloads the files of `album` missing from `dir`, each named by its slug.
+///
+/// Pulled slugs are kept in `dir/.cbx-pull.json`, so a file is never fetched again unless it was
+/// deleted locally. Catbox files can't change, so the slug is enough to know the contents.
+///
+/// With `delete`, pulled files that left the album are removed, files the state doesn't know about
+/// are never touched.
+pub async fn pull(
+    client: &CatboxClient,
+    album: &Album,
+    dir: &Path,
+    delete: bool,
+) -> Result<PullReport, DownloadError> {
+    let state_path = dir.join(PULL_STATE);
+    fs::create_dir_all(dir)
+        .await
+        .map_err(|source| DownloadError::WriteFile {
+            source,
+            file: dir.to_path_buf(),
+        })?;
+
+    let mut state = match fs::read(&state_path).await {
+        Ok(x) => serde_json::from_slice::<PullState>(&x).map_err(|source| {
+            DownloadError::InvalidState {
+                source,
+                file: state_path.clone(),
+            }
+        })?,
+        Err(x) if x.kind() == IoErrorKind::NotFound => PullState {
+            album: album.url.clone(),
+            files: BTreeSet::new(),
+        },
+        Err(source) => {
+            return Err(DownloadError::ReadState {
+                source,
+                file: state_path,
+            })
+        }
+    };
+    ensure!(
+        state.album == album.url,
+        DownloadError::StateOfOtherAlbum {
+            file: state_path,
+            album: state.album,
+        }
+    );
+
+    let files = client.album_files(album).await?;
+    let mut report = PullReport::default();
+
+    for file in &files.files {
+        let slug = file.slug();
+        let path = dir.join(&slug);
+        if state.files.contains(&slug) && fs::try_exists(&path).await.unwrap_or(false) {
+            report.unchanged += 1;
+            continue;
+        }
+
+        client.download(file.url.clone(), &path).await?;
+        state.files.insert(slug);
+        save_state(&state_path, &state).await?;
+        report.downloaded.push(path);
+    }
+
+    if delete {
+        let current = files
+            .files
+            .iter()
+            .map(UploadedFile::slug)
+            .collect::<HashSet<_>>();
+        let gone = state
+            .files
+            .iter()
+            .filter(|x| !current.contains(*x))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for slug in gone {
+            let path = dir.join(&slug);
+            match fs::remove_file(&path).await {
+                Ok(()) => report.removed.push(path),
+                Err(x) if x.kind() == IoErrorKind::NotFound => {}
+                Err(source) => return Err(DownloadError::RemoveFile { source, file: path }),
+            }
+            state.files.remove(&slug);
+        }
+    }
+    save_state(&state_path, &state).await?;
+
+    Ok(report)
+}
+
+async fn save_state(path: &Path, state: &PullState) -> Result<(), DownloadError> {
+    let json = serde_json::to_vec_pretty(state).expect("pull state serializes");
+    fs::write(path, json)
+        .await
+        .map_err(|source| DownloadError::WriteFile {
+            source,
+            file: path.to_path_buf(),
+        })
+}
+
+async fn hash_file(path: &Path) -> Result<String, UploadFileError> {
+    let read_error = |source| UploadFileError::ReadFile {
+        source,
+        file: path.to_path_buf(),
+    };
+
+    let mut stream = ReaderStream::new(File::open(path).await.map_err(read_error)?);
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = stream.try_next().await.map_err(read_error)? {
+        hasher.update(&chunk);
+    }
+
+    Ok(to_hex(&hasher.finalize()))
+}
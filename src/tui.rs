@@ -0,0 +1,765 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use catbox::{
+    album::{Album, Files},
+    file::{format_size, UploadedFile, DATE_FORMAT},
+    filter::{FileFilter, NameMatcher},
+    progress::{ChannelReporter, ProgressEvent, TaskId},
+    upload::UploadOptions,
+    AppError, CatboxClient,
+};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{
+        Block, Cell, Clear, Gauge, List, ListItem, ListState, Paragraph, Row, Table, TableState,
+    },
+    DefaultTerminal, Frame,
+};
+use reqwest::Url;
+use tokio::sync::mpsc;
+
+/// At most this many running operations get a progress bar, the rest are counted.
+const MAX_PROGRESS: usize = 4;
+
+const LOADING: &str = "Loading...";
+
+const KEYS: &str = "q quit · tab switch pane · / filter · y copy url · d download · x delete · a add to album · u upload · r refresh";
+
+/// What the terminal and the background operations send to the interface.
+enum Message {
+    Key(KeyEvent),
+    Redraw,
+    Albums(Vec<Album>),
+    Listing(Vec<UploadedFile>),
+    AlbumFiles(Url, Vec<UploadedFile>),
+    /// an operation finished without changing anything on catbox
+    Done(String),
+    /// an operation changed the account, everything is fetched again
+    Changed(String),
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Albums,
+    Files,
+}
+
+enum Mode {
+    Normal,
+    Filter,
+    Confirm(Confirm),
+    PickAlbum(ListState),
+    PickFile(FilePicker),
+}
+
+/// An operation that waits for `y` before it runs.
+enum Confirm {
+    DeleteFile(UploadedFile),
+    DeleteAlbum(Album),
+}
+
+impl Confirm {
+    fn question(&self) -> String {
+        match self {
+            Self::DeleteFile(file) => format!("Delete {} from your account?", name(file)),
+            Self::DeleteAlbum(album) => format!("Delete album {}?", title(album)),
+        }
+    }
+}
+
+/// Browses local directories for a file to upload.
+struct FilePicker {
+    dir: PathBuf,
+    /// directories first, `..` leads to the parent
+    entries: Vec<(String, PathBuf)>,
+    state: ListState,
+}
+
+impl FilePicker {
+    fn open(dir: PathBuf) -> Self {
+        let mut entries = std::fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .map(|x| {
+                let path = x.path();
+                let mut label = x.file_name().to_string_lossy().into_owned();
+                if path.is_dir() {
+                    label.push('/');
+                }
+                (label, path)
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(label, path)| (!path.is_dir(), label.to_lowercase()));
+        if let Some(parent) = dir.parent() {
+            entries.insert(0, ("../".to_owned(), parent.to_path_buf()));
+        }
+
+        let mut state = ListState::default();
+        state.select((!entries.is_empty()).then_some(0));
+        Self {
+            dir,
+            entries,
+            state,
+        }
+    }
+}
+
+/// A running operation, as reported by the client.
+struct Progress {
+    label: String,
+    total: Option<u64>,
+    sent: u64,
+}
+
+struct App {
+    client: CatboxClient,
+    options: UploadOptions,
+    sender: mpsc::UnboundedSender<Message>,
+    albums: Vec<Album>,
+    /// 0 is every file of the account, the albums follow
+    album_state: ListState,
+    listing: Vec<UploadedFile>,
+    album_files: HashMap<Url, Vec<UploadedFile>>,
+    loading: HashSet<Url>,
+    /// files of the selected album or the account, after filtering
+    files: Vec<UploadedFile>,
+    file_state: TableState,
+    filter: String,
+    pane: Pane,
+    mode: Mode,
+    tasks: BTreeMap<TaskId, Progress>,
+    status: String,
+    quit: bool,
+}
+
+/// Runs the terminal interface until it is quit.
+///
+/// Operations run in the background, their progress is drawn at the bottom of the interface.
+pub async fn run(client: &CatboxClient, options: UploadOptions) -> Result<(), AppError> {
+    let (reporter, mut progress) = ChannelReporter::new();
+    let (sender, mut messages) = mpsc::unbounded_channel();
+
+    let input = sender.clone();
+    std::thread::spawn(move || loop {
+        let message = match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => Message::Key(key),
+            Ok(Event::Resize(..)) => Message::Redraw,
+            Ok(_) => continue,
+            Err(_) => break,
+        };
+        if input.send(message).is_err() {
+            break;
+        }
+    });
+
+    let mut app = App {
+        client: client.with_progress(Arc::new(reporter)),
+        options,
+        sender,
+        albums: Vec::new(),
+        album_state: ListState::default().with_selected(Some(0)),
+        listing: Vec::new(),
+        album_files: HashMap::new(),
+        loading: HashSet::new(),
+        files: Vec::new(),
+        file_state: TableState::default(),
+        filter: String::new(),
+        pane: Pane::Files,
+        mode: Mode::Normal,
+        tasks: BTreeMap::new(),
+        status: LOADING.to_owned(),
+        quit: false,
+    };
+    app.reload(false);
+
+    let mut terminal = ratatui::try_init().map_err(AppError::Terminal)?;
+    let result = app
+        .event_loop(&mut terminal, &mut messages, &mut progress)
+        .await;
+    ratatui::try_restore().map_err(AppError::Terminal)?;
+    result
+}
+
+impl App {
+    async fn event_loop(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        messages: &mut mpsc::UnboundedReceiver<Message>,
+        progress: &mut mpsc::UnboundedReceiver<ProgressEvent>,
+    ) -> Result<(), AppError> {
+        while !self.quit {
+            terminal
+                .draw(|frame| self.draw(frame))
+                .map_err(AppError::Terminal)?;
+
+            tokio::select! {
+                Some(message) = messages.recv() => self.handle(message),
+                Some(event) = progress.recv() => self.progress(event),
+                else => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `operation` in the background, its outcome comes back as a message.
+    fn spawn(&self, operation: impl Future<Output = Message> + Send + 'static) {
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            let _ = sender.send(operation.await);
+        });
+    }
+
+    /// Loads the albums, the account listing and the selected album again.
+    ///
+    /// The listings come from the listing cache while it is fresh, unless `refresh` is set.
+    fn reload(&mut self, refresh: bool) {
+        self.album_files.clear();
+        self.loading.clear();
+
+        let client = self.client.clone();
+        self.spawn(async move {
+            let albums = if refresh {
+                client.refresh_albums().await
+            } else {
+                client.list_albums().await
+            };
+            albums.map_or_else(|x| Message::Failed(x.to_string()), Message::Albums)
+        });
+        let client = self.client.clone();
+        self.spawn(async move {
+            let files = if refresh {
+                client.refresh_files().await
+            } else {
+                client.list_files().await
+            };
+            files.map_or_else(|x| Message::Failed(x.to_string()), Message::Listing)
+        });
+        self.load_album();
+    }
+
+    /// Fetches the files of the selected album, unless they are known or on their way.
+    fn load_album(&mut self) {
+        let Some(album) = self.selected_album().cloned() else {
+            return;
+        };
+        if self.album_files.contains_key(&album.url) || !self.loading.insert(album.url.clone()) {
+            return;
+        }
+
+        let client = self.client.clone();
+        self.spawn(async move {
+            client.album_files(&album).await.map_or_else(
+                |x| Message::Failed(x.to_string()),
+                |x| Message::AlbumFiles(album.url, x.files),
+            )
+        });
+    }
+
+    fn selected_album(&self) -> Option<&Album> {
+        self.album_state
+            .selected()
+            .and_then(|x| x.checked_sub(1))
+            .and_then(|x| self.albums.get(x))
+    }
+
+    fn selected_file(&self) -> Option<&UploadedFile> {
+        self.file_state.selected().and_then(|x| self.files.get(x))
+    }
+
+    /// Recomputes the shown files after the selection, the filter or the data changed.
+    fn refresh_files(&mut self) {
+        let files = match self.selected_album() {
+            Some(album) => self.album_files.get(&album.url).map_or_else(Vec::new, |x| {
+                Files { files: x.clone() }
+                    .with_metadata(self.listing.iter().cloned())
+                    .files
+            }),
+            None => self.listing.clone(),
+        };
+        let filter = FileFilter {
+            name: (!self.filter.is_empty()).then(|| NameMatcher::substring(&self.filter)),
+            ..FileFilter::default()
+        };
+        self.files = files.into_iter().filter(|x| filter.matches(x)).collect();
+
+        let selected = self.file_state.selected().unwrap_or_default();
+        self.file_state
+            .select((!self.files.is_empty()).then(|| selected.min(self.files.len() - 1)));
+    }
+
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::Key(key) => self.key(key),
+            Message::Redraw => {}
+            Message::Albums(albums) => {
+                self.albums = albums;
+                let selected = self.album_state.selected().unwrap_or_default();
+                self.album_state
+                    .select(Some(selected.min(self.albums.len())));
+                self.load_album();
+                self.refresh_files();
+            }
+            Message::Listing(listing) => {
+                self.listing = listing;
+                if self.status == LOADING {
+                    self.status.clear();
+                }
+                self.refresh_files();
+            }
+            Message::AlbumFiles(url, files) => {
+                self.loading.remove(&url);
+                self.album_files.insert(url, files);
+                self.refresh_files();
+            }
+            Message::Done(status) => self.status = status,
+            Message::Changed(status) => {
+                self.status = status;
+                self.reload(false);
+            }
+            Message::Failed(error) => self.status = format!("Error: {error}"),
+        }
+    }
+
+    fn progress(&mut self, event: ProgressEvent) {
+        match event {
+            ProgressEvent::Started {
+                task, label, total, ..
+            } => {
+                self.tasks.insert(
+                    task,
+                    Progress {
+                        label,
+                        total,
+                        sent: 0,
+                    },
+                );
+            }
+            ProgressEvent::BytesSent { task, bytes } => {
+                if let Some(progress) = self.tasks.get_mut(&task) {
+                    progress.sent += bytes;
+                }
+            }
+            ProgressEvent::Finished { task } | ProgressEvent::Failed { task, .. } => {
+                self.tasks.remove(&task);
+            }
+        }
+    }
+
+    fn key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+
+        match std::mem::replace(&mut self.mode, Mode::Normal) {
+            Mode::Normal => self.normal_key(key),
+            Mode::Filter => match key.code {
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.refresh_files();
+                }
+                KeyCode::Enter => {}
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                    self.refresh_files();
+                    self.mode = Mode::Filter;
+                }
+                KeyCode::Char(x) => {
+                    self.filter.push(x);
+                    self.refresh_files();
+                    self.mode = Mode::Filter;
+                }
+                _ => self.mode = Mode::Filter,
+            },
+            Mode::Confirm(confirm) => match key.code {
+                KeyCode::Char('y' | 'Y') => self.confirmed(confirm),
+                _ => self.status = "Cancelled".to_owned(),
+            },
+            Mode::PickAlbum(mut state) => match key.code {
+                KeyCode::Enter => {
+                    let album = state.selected().and_then(|x| self.albums.get(x)).cloned();
+                    if let (Some(album), Some(file)) = (album, self.selected_file()) {
+                        self.add_to_album(album, file.slug());
+                    }
+                }
+                KeyCode::Esc | KeyCode::Char('q') => {}
+                code => {
+                    state.select(step(state.selected(), self.albums.len(), code));
+                    self.mode = Mode::PickAlbum(state);
+                }
+            },
+            Mode::PickFile(mut picker) => match key.code {
+                KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => {
+                    match picker.state.selected().and_then(|x| picker.entries.get(x)) {
+                        Some((_, path)) if path.is_dir() => {
+                            self.mode = Mode::PickFile(FilePicker::open(path.clone()));
+                        }
+                        Some((_, path)) => self.upload(path.clone()),
+                        None => self.mode = Mode::PickFile(picker),
+                    }
+                }
+                KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h') => {
+                    let parent = picker.dir.parent().map(Path::to_path_buf);
+                    self.mode = Mode::PickFile(parent.map_or(picker, FilePicker::open));
+                }
+                KeyCode::Esc | KeyCode::Char('q') => {}
+                code => {
+                    let selected = step(picker.state.selected(), picker.entries.len(), code);
+                    picker.state.select(selected);
+                    self.mode = Mode::PickFile(picker);
+                }
+            },
+        }
+    }
+
+    fn normal_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Esc => {
+                self.filter.clear();
+                self.refresh_files();
+            }
+            KeyCode::Tab | KeyCode::BackTab | KeyCode::Left | KeyCode::Right => {
+                self.pane = match self.pane {
+                    Pane::Albums => Pane::Files,
+                    Pane::Files => Pane::Albums,
+                };
+            }
+            KeyCode::Char('h') => self.pane = Pane::Albums,
+            KeyCode::Char('l') => self.pane = Pane::Files,
+            KeyCode::Enter if self.pane == Pane::Albums => self.pane = Pane::Files,
+            KeyCode::Char('/') => self.mode = Mode::Filter,
+            KeyCode::Char('r') | KeyCode::F(5) => {
+                self.status = LOADING.to_owned();
+                self.reload(true);
+            }
+            KeyCode::Char('u') => {
+                let dir = std::env::current_dir().unwrap_or_default();
+                self.mode = Mode::PickFile(FilePicker::open(dir));
+            }
+            KeyCode::Char('y') => self.copy(),
+            KeyCode::Char('d') if self.pane == Pane::Files => self.download(),
+            KeyCode::Char('x') | KeyCode::Delete => {
+                let confirm = match self.pane {
+                    Pane::Albums => self.selected_album().cloned().map(Confirm::DeleteAlbum),
+                    Pane::Files => self.selected_file().cloned().map(Confirm::DeleteFile),
+                };
+                if let Some(confirm) = confirm {
+                    self.mode = Mode::Confirm(confirm);
+                }
+            }
+            KeyCode::Char('a') if self.pane == Pane::Files => {
+                if self.selected_file().is_some() && !self.albums.is_empty() {
+                    self.mode = Mode::PickAlbum(ListState::default().with_selected(Some(0)));
+                }
+            }
+            code => match self.pane {
+                Pane::Albums => {
+                    let selected = step(self.album_state.selected(), self.albums.len() + 1, code);
+                    if selected != self.album_state.selected() {
+                        self.album_state.select(selected);
+                        self.file_state.select(Some(0));
+                        self.load_album();
+                        self.refresh_files();
+                    }
+                }
+                Pane::Files => {
+                    let selected = step(self.file_state.selected(), self.files.len(), code);
+                    self.file_state.select(selected);
+                }
+            },
+        }
+    }
+
+    /// Puts the url of the selection on the clipboard, through the terminal(OSC 52).
+    fn copy(&mut self) {
+        let url = match self.pane {
+            Pane::Albums => self.selected_album().map(|x| x.url.to_string()),
+            Pane::Files => self.selected_file().map(|x| x.url.to_string()),
+        };
+        let Some(url) = url else {
+            return;
+        };
+
+        let mut stdout = std::io::stdout();
+        let copied =
+            write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(&url)).and_then(|()| stdout.flush());
+        self.status = match copied {
+            Ok(()) => format!("Copied {url}"),
+            Err(error) => format!("Error: Fails to copy {url}: {error}"),
+        };
+    }
+
+    /// Downloads the selected file into the current directory, named like it was uploaded.
+    ///
+    /// Only the last component of the name is used, and existing files are never overwritten.
+    fn download(&mut self) {
+        let Some(file) = self.selected_file().cloned() else {
+            return;
+        };
+        let (name, slug) = (name(&file), file.slug());
+        let candidates =
+            [name.as_str(), slug.as_str()].map(|x| Path::new(x).file_name().map(PathBuf::from));
+        let Some(path) = candidates.iter().flatten().find(|x| !x.exists()).cloned() else {
+            self.status = format!("Not downloading {slug}, the file already exists");
+            return;
+        };
+
+        let client = self.client.clone();
+        self.spawn(async move {
+            match client.download(file.url, &path).await {
+                Ok(written) => Message::Done(format!(
+                    "Saved {} ({})",
+                    path.display(),
+                    format_size(written)
+                )),
+                Err(error) => Message::Failed(error.to_string()),
+            }
+        });
+    }
+
+    fn confirmed(&mut self, confirm: Confirm) {
+        let client = self.client.clone();
+        match confirm {
+            Confirm::DeleteFile(file) => self.spawn(async move {
+                match client.delete_files(&[&file.slug()]).await {
+                    Ok(()) => Message::Changed(format!("Deleted {}", name(&file))),
+                    Err(error) => Message::Failed(error.to_string()),
+                }
+            }),
+            Confirm::DeleteAlbum(album) => self.spawn(async move {
+                match client.delete_album(&album).await {
+                    Ok(()) => Message::Changed(format!("Deleted album {}", title(&album))),
+                    Err(error) => Message::Failed(error.to_string()),
+                }
+            }),
+        }
+    }
+
+    fn add_to_album(&mut self, album: Album, slug: String) {
+        let client = self.client.clone();
+        self.spawn(async move {
+            match client.add_to_album(&album, &slug).await {
+                Ok(()) => Message::Changed(format!("Added {slug} to {}", title(&album))),
+                Err(error) => Message::Failed(error.to_string()),
+            }
+        });
+    }
+
+    /// Uploads `path` to the account, and into the selected album if there is one.
+    fn upload(&mut self, path: PathBuf) {
+        let client = self.client.clone();
+        let options = self.options.clone();
+        let album = self.selected_album().cloned();
+        self.spawn(async move {
+            let uploaded = match client.upload(&path, &options).await {
+                Ok(x) => x,
+                Err(error) => return Message::Failed(error.to_string()),
+            };
+            if let Some(album) = album {
+                if let Err(error) = client
+                    .add_to_album(&album, &client.file_slug(&uploaded.url))
+                    .await
+                {
+                    return Message::Failed(error.to_string());
+                }
+            }
+            Message::Changed(format!("Uploaded {}: {}", path.display(), uploaded.url))
+        });
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let progress_height = self.tasks.len().min(MAX_PROGRESS) as u16;
+        let [main, progress, status, keys] = Layout::vertical([
+            Constraint::Min(0),
+            Constraint::Length(progress_height),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [albums, files] =
+            Layout::horizontal([Constraint::Percentage(30), Constraint::Percentage(70)])
+                .areas(main);
+
+        self.draw_albums(frame, albums);
+        self.draw_files(frame, files);
+        self.draw_progress(frame, progress);
+
+        let status_line = match &self.mode {
+            Mode::Filter => Line::from(format!("/{}▏", self.filter)),
+            _ => Line::from(self.status.as_str()),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+        frame.render_widget(Paragraph::new(Line::from(KEYS).dark_gray()), keys);
+
+        match &mut self.mode {
+            Mode::Normal | Mode::Filter => {}
+            Mode::Confirm(confirm) => {
+                let question = format!("{} (y/n)", confirm.question());
+                let area = popup(frame.area(), question.len() as u16 + 4, 3);
+                frame.render_widget(Clear, area);
+                frame.render_widget(Paragraph::new(question).block(Block::bordered()), area);
+            }
+            Mode::PickAlbum(state) => {
+                let area = popup(frame.area(), 50, self.albums.len() as u16 + 2);
+                let list = List::new(self.albums.iter().map(|x| ListItem::new(title(x))))
+                    .block(Block::bordered().title(" Add to album "))
+                    .highlight_style(Style::new().reversed());
+                frame.render_widget(Clear, area);
+                frame.render_stateful_widget(list, area, state);
+            }
+            Mode::PickFile(picker) => {
+                let area = popup(frame.area(), 70, 20);
+                let list = List::new(
+                    picker
+                        .entries
+                        .iter()
+                        .map(|(x, _)| ListItem::new(x.as_str())),
+                )
+                .block(Block::bordered().title(format!(" Upload {} ", picker.dir.display())))
+                .highlight_style(Style::new().reversed());
+                frame.render_widget(Clear, area);
+                frame.render_stateful_widget(list, area, &mut picker.state);
+            }
+        }
+    }
+
+    fn draw_albums(&mut self, frame: &mut Frame, area: Rect) {
+        let items = std::iter::once(ListItem::new(format!("All files ({})", self.listing.len())))
+            .chain(self.albums.iter().map(|x| {
+                let count = x.file_count.map(|x| format!(" ({x})")).unwrap_or_default();
+                ListItem::new(format!("{}{count}", title(x)))
+            }));
+        let list = List::new(items)
+            .block(pane_block(" Albums ", self.pane == Pane::Albums))
+            .highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(list, area, &mut self.album_state);
+    }
+
+    fn draw_files(&mut self, frame: &mut Frame, area: Rect) {
+        let mut heading = match self.selected_album() {
+            Some(album) if self.loading.contains(&album.url) => {
+                format!(" {} (loading) ", title(album))
+            }
+            Some(album) => format!(" {} ", title(album)),
+            None => " All files ".to_owned(),
+        };
+        if !self.filter.is_empty() {
+            heading.push_str(&format!("/{} ", self.filter));
+        }
+
+        let rows = self.files.iter().map(|x| {
+            Row::new([
+                Cell::from(name(x)),
+                Cell::from(x.size.map(format_size).unwrap_or_default()),
+                Cell::from(
+                    x.date
+                        .map(|x| x.format(DATE_FORMAT).to_string())
+                        .unwrap_or_default(),
+                ),
+                Cell::from(x.slug()),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(10),
+                Constraint::Length(19),
+                Constraint::Length(16),
+            ],
+        )
+        .header(Row::new(["Name", "Size", "Date", "Slug"]).bold())
+        .block(pane_block(&heading, self.pane == Pane::Files))
+        .row_highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(table, area, &mut self.file_state);
+    }
+
+    fn draw_progress(&self, frame: &mut Frame, area: Rect) {
+        let rows = Layout::vertical(vec![Constraint::Length(1); area.height as usize]).split(area);
+        let hidden = self.tasks.len().saturating_sub(MAX_PROGRESS);
+
+        for (progress, row) in self.tasks.values().zip(rows.iter()) {
+            let label = match progress.total {
+                Some(total) => format!(
+                    "{} {}/{}",
+                    progress.label,
+                    format_size(progress.sent),
+                    format_size(total)
+                ),
+                None => format!("{} {}", progress.label, format_size(progress.sent)),
+            };
+            let label = if hidden > 0 && row == rows.last().unwrap_or(row) {
+                format!("{label} (+{hidden} more)")
+            } else {
+                label
+            };
+            let ratio = progress
+                .total
+                .filter(|x| *x > 0)
+                .map_or(0.0, |x| (progress.sent as f64 / x as f64).min(1.0));
+            frame.render_widget(
+                Gauge::default()
+                    .ratio(ratio)
+                    .label(label)
+                    .gauge_style(Style::new().fg(Color::Cyan).bg(Color::DarkGray)),
+                *row,
+            );
+        }
+    }
+}
+
+/// Moves a selection of a list with `len` entries by the pressed key.
+fn step(selected: Option<usize>, len: usize, code: KeyCode) -> Option<usize> {
+    let last = len.checked_sub(1)?;
+    let selected = selected.unwrap_or_default();
+    Some(match code {
+        KeyCode::Down | KeyCode::Char('j') => selected.saturating_add(1).min(last),
+        KeyCode::Up | KeyCode::Char('k') => selected.saturating_sub(1),
+        KeyCode::PageDown => selected.saturating_add(10).min(last),
+        KeyCode::PageUp => selected.saturating_sub(10),
+        KeyCode::Home | KeyCode::Char('g') => 0,
+        KeyCode::End | KeyCode::Char('G') => last,
+        _ => selected.min(last),
+    })
+}
+
+fn pane_block(title: &str, focused: bool) -> Block<'static> {
+    let block = Block::bordered().title(title.to_owned());
+    if focused {
+        block.border_style(Style::new().fg(Color::Cyan))
+    } else {
+        block
+    }
+}
+
+/// A `width` by `height` area in the middle of `area`.
+fn popup(area: Rect, width: u16, height: u16) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Length(width)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(area);
+    area
+}
+
+fn name(file: &UploadedFile) -> String {
+    file.name.clone().unwrap_or_else(|| file.slug())
+}
+
+fn title(album: &Album) -> String {
+    album.title.clone().unwrap_or_else(|| album.url.to_string())
+}
@@ -1,17 +1,82 @@
-use std::{path::Path, str::FromStr, time::Duration};
+use std::{
+    fmt::Write,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
 
-use futures_util::TryStreamExt;
-use indicatif::{ProgressBar, ProgressStyle};
+use bytes::Bytes;
+use futures_util::{Stream, TryStreamExt};
 use reqwest::{
     multipart::{self, Part},
-    Body, Client,
+    Body,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
 };
-use tokio::fs::File;
 use tokio_util::io::ReaderStream;
 
-use crate::{network::create_spoof_client, user, NetworkError, UploadFileError, MULTI_PROGRESS};
+use crate::{
+    archive::{stream_archive, walk_files, ArchiveOptions},
+    client::Context,
+    metadata::{strip_metadata, ImageFormat},
+    progress::{Task, TaskKind},
+    NetworkError, UploadFileError,
+};
+
+/// Size of the chunks an in-memory upload body is split into, so progress can be tracked.
+const CHUNK_SIZE: usize = 64 * 1024;
 
-const LITTER_API_URL: &str = "https://litterbox.catbox.moe/resources/internals/api.php";
+/// Options shared by every upload.
+#[derive(Debug, Clone, Default)]
+pub struct UploadOptions {
+    /// strip EXIF/XMP/ICC metadata from JPEG, PNG and WebP files before uploading
+    pub strip_metadata: bool,
+    /// how directories are packed
+    pub archive: ArchiveOptions,
+}
+
+/// A finished upload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Uploaded {
+    pub url: String,
+    /// amount of bytes sent, after stripping metadata or archiving
+    pub size: u64,
+    /// hex encoded SHA-256 of the bytes sent
+    pub sha256: String,
+}
+
+/// Hashes a request body while it is being sent.
+#[derive(Clone, Default)]
+struct BodyDigest(Arc<Mutex<(Sha256, u64)>>);
+
+impl BodyDigest {
+    fn update(&self, bytes: &[u8]) {
+        let mut digest = self.0.lock().expect("digest poisoned");
+        digest.0.update(bytes);
+        digest.1 += bytes.len() as u64;
+    }
+
+    fn finish(self, url: String) -> Uploaded {
+        let (hasher, size) = std::mem::take(&mut *self.0.lock().expect("digest poisoned"));
+        let sha256 = to_hex(&hasher.finalize());
+        Uploaded { url, size, sha256 }
+    }
+}
+
+/// Lowercase hex encoding of a digest.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut hex, x| {
+            let _ = write!(hex, "{x:02x}");
+            hex
+        })
+}
 
 pub enum UploadTarget {
     Catbox { user_hash: String },
@@ -19,7 +84,8 @@ pub enum UploadTarget {
 }
 
 impl LitterExpiry {
-    const fn as_str(&self) -> &'static str {
+    /// The value Litterbox and `--expiry` take, e.g. `12h`.
+    pub const fn as_str(&self) -> &'static str {
         match self {
             Self::OneHour => "1h",
             Self::TwelveHours => "12h",
@@ -27,6 +93,16 @@ impl LitterExpiry {
             Self::ThreeDays => "72h",
         }
     }
+
+    /// How long Litterbox keeps the upload.
+    pub const fn hours(self) -> i64 {
+        match self {
+            Self::OneHour => 1,
+            Self::TwelveHours => 12,
+            Self::OneDay => 24,
+            Self::ThreeDays => 72,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -55,72 +131,239 @@ impl FromStr for LitterExpiry {
 
 pub async fn upload_file(
     path: impl AsRef<Path> + Send,
+    options: &UploadOptions,
     target: UploadTarget,
-    client: &Client,
-) -> Result<String, UploadFileError> {
+    context: &Context,
+) -> Result<Uploaded, UploadFileError> {
     let path = path.as_ref();
 
-    let file = File::open(path)
-        .await
-        .map_err(|source| UploadFileError::ReadFile {
-            file: path.to_path_buf(),
-            source,
-        })?;
+    let read_error = |source| UploadFileError::ReadFile {
+        file: path.to_path_buf(),
+        source,
+    };
 
-    let total_bytes = file
-        .metadata()
-        .await
-        .map_err(|source| UploadFileError::ReadFile {
-            file: path.to_path_buf(),
-            source,
-        })?
-        .len();
-
-    let bar = ProgressBar::new(total_bytes).with_prefix(path.to_string_lossy().to_string());
-
-    bar.set_style(
-        #[allow(clippy::literal_string_with_formatting_args)]
-        ProgressStyle::with_template(
-            "{prefix:.magenta}\n[ETA: {eta}] [{decimal_bytes_per_sec:}] [{elapsed_precise}] {wide_bar:.cyan/blue} {decimal_bytes}/{decimal_total_bytes}",
-        )
-        .expect("Invalid template(compile time issue)")
-        .progress_chars("##-"),
-    );
+    let mut file = File::open(path).await.map_err(read_error)?;
 
-    MULTI_PROGRESS.add(bar.clone());
+    let file_name = path
+        .file_name()
+        .ok_or(UploadFileError::InvalidFilename)?
+        .to_string_lossy()
+        .to_string();
 
-    bar.enable_steady_tick(Duration::from_millis(500));
+    if options.strip_metadata {
+        let mut magic = Vec::with_capacity(12);
+        (&mut file)
+            .take(12)
+            .read_to_end(&mut magic)
+            .await
+            .map_err(read_error)?;
 
-    let bar_cloned = bar.clone();
+        if ImageFormat::detect(&magic).is_some() {
+            let mut bytes = magic;
+            file.read_to_end(&mut bytes).await.map_err(read_error)?;
+
+            if let Some(stripped) =
+                strip_metadata(&bytes).map_err(|reason| UploadFileError::MalformedImage {
+                    file: path.to_path_buf(),
+                    reason,
+                })?
+            {
+                return upload_bytes(path, file_name, stripped.into(), target, context).await;
+            }
+        }
+
+        file.seek(SeekFrom::Start(0)).await.map_err(read_error)?;
+    }
+
+    let total_bytes = file.metadata().await.map_err(read_error)?.len();
+
+    let task = upload_task(context, path, total_bytes);
+
+    let task_cloned = task.clone();
+    let digest = BodyDigest::default();
+    let digest_cloned = digest.clone();
 
     let stream = ReaderStream::new(file).inspect_ok(move |x| {
-        bar_cloned.inc(x.len() as u64);
+        digest_cloned.update(x);
+        task_cloned.advance(x.len() as u64);
     });
 
     let body_stream = Body::wrap_stream(stream);
 
-    let mut form = multipart::Form::new().text("reqtype", "fileupload").part(
-        "fileToUpload",
-        Part::stream_with_length(body_stream, total_bytes).file_name(
-            path.file_name()
-                .ok_or(UploadFileError::InvalidFilename)?
-                .to_string_lossy()
-                .to_string(),
-        ),
+    let part = Part::stream_with_length(body_stream, total_bytes).file_name(file_name);
+
+    task.complete(send_upload(part, target, context).await)
+        .map(|url| digest.finish(url))
+}
+
+/// Uploads an in-memory file named `file_name`, e.g. one read from stdin.
+pub async fn upload_data(
+    file_name: String,
+    bytes: Bytes,
+    options: &UploadOptions,
+    target: UploadTarget,
+    context: &Context,
+) -> Result<Uploaded, UploadFileError> {
+    let bytes = if options.strip_metadata {
+        strip_metadata(&bytes)
+            .map_err(|reason| UploadFileError::MalformedImage {
+                file: PathBuf::from(&file_name),
+                reason,
+            })?
+            .map_or(bytes, Bytes::from)
+    } else {
+        bytes
+    };
+
+    let label = PathBuf::from(&file_name);
+    upload_bytes(&label, file_name, bytes, target, context).await
+}
+
+/// Uploads an in-memory file, the reported total is the size of `bytes`.
+async fn upload_bytes(
+    path: &Path,
+    file_name: String,
+    bytes: Bytes,
+    target: UploadTarget,
+    context: &Context,
+) -> Result<Uploaded, UploadFileError> {
+    let total_bytes = bytes.len() as u64;
+
+    let task = upload_task(context, path, total_bytes);
+
+    let task_cloned = task.clone();
+    let digest = BodyDigest::default();
+    let digest_cloned = digest.clone();
+
+    let chunks = (0..bytes.len())
+        .step_by(CHUNK_SIZE)
+        .map(move |x| Ok::<_, std::io::Error>(bytes.slice(x..bytes.len().min(x + CHUNK_SIZE))));
+
+    let stream = futures_util::stream::iter(chunks).inspect_ok(move |x| {
+        digest_cloned.update(x);
+        task_cloned.advance(x.len() as u64);
+    });
+
+    let part =
+        Part::stream_with_length(Body::wrap_stream(stream), total_bytes).file_name(file_name);
+
+    task.complete(send_upload(part, target, context).await)
+        .map(|url| digest.finish(url))
+}
+
+/// Uploads a stream of unknown length as a file named `file_name`, without buffering it.
+///
+/// Used to pass an upload through, e.g. one received by `cbx serve`.
+pub async fn upload_stream<S>(
+    file_name: String,
+    stream: S,
+    target: UploadTarget,
+    context: &Context,
+) -> Result<Uploaded, UploadFileError>
+where
+    S: Stream<Item = Result<Bytes, std::io::Error>> + Send + 'static,
+{
+    let task = Task::start(context.progress(), TaskKind::Upload, &file_name, None);
+
+    let task_cloned = task.clone();
+    let digest = BodyDigest::default();
+    let digest_cloned = digest.clone();
+
+    let stream = stream.inspect_ok(move |x| {
+        digest_cloned.update(x);
+        task_cloned.advance(x.len() as u64);
+    });
+
+    let part = Part::stream(Body::wrap_stream(stream)).file_name(file_name);
+
+    task.complete(send_upload(part, target, context).await)
+        .map(|url| digest.finish(url))
+}
+
+/// Uploads a directory as a single archive.
+///
+/// The archive is built while the request body is being sent, so no temporary file is created.
+/// Progress is reported in uncompressed bytes read from the directory.
+pub async fn upload_directory(
+    path: impl AsRef<Path> + Send,
+    options: &UploadOptions,
+    target: UploadTarget,
+    context: &Context,
+) -> Result<Uploaded, UploadFileError> {
+    let path = path.as_ref();
+
+    let options = &options.archive;
+
+    let files = walk_files(path, &options.excludes)?;
+
+    let total_bytes = files
+        .iter()
+        .map(|x| {
+            std::fs::metadata(path.join(x))
+                .map(|x| x.len())
+                .map_err(|source| UploadFileError::ReadFile {
+                    file: path.join(x),
+                    source,
+                })
+        })
+        .sum::<Result<u64, _>>()?;
+
+    let file_name = format!(
+        "{}.{}",
+        path.file_name()
+            .ok_or(UploadFileError::InvalidFilename)?
+            .to_string_lossy(),
+        options.format.extension()
     );
 
+    let task = upload_task(context, path, total_bytes);
+
+    let task_cloned = task.clone();
+    let digest = BodyDigest::default();
+    let digest_cloned = digest.clone();
+
+    let stream = stream_archive(path.to_path_buf(), files, options.format, move |x| {
+        task_cloned.advance(x);
+    })
+    .inspect_ok(move |x| digest_cloned.update(x));
+
+    let part = Part::stream(Body::wrap_stream(stream)).file_name(file_name);
+
+    task.complete(send_upload(part, target, context).await)
+        .map(|url| digest.finish(url))
+}
+
+fn upload_task(context: &Context, path: &Path, total_bytes: u64) -> Task {
+    Task::start(
+        context.progress(),
+        TaskKind::Upload,
+        path.display(),
+        Some(total_bytes),
+    )
+}
+
+async fn send_upload(
+    part: Part,
+    target: UploadTarget,
+    context: &Context,
+) -> Result<String, UploadFileError> {
+    let mut form = multipart::Form::new()
+        .text("reqtype", "fileupload")
+        .part("fileToUpload", part);
+
     let api = match target {
         UploadTarget::Catbox { user_hash } => {
             form = form.text("userhash", user_hash);
-            user::API_URL
+            context.endpoints().api()
         }
         UploadTarget::Litterbox { expiry } => {
             form = form.text("time", expiry.as_str());
-            LITTER_API_URL
+            context.endpoints().litter_api()
         }
     };
 
-    let resp = client
+    let resp = context
+        .http()
         .post(api)
         .multipart(form)
         .send()
@@ -135,14 +378,40 @@ pub async fn upload_file(
         return Err(UploadFileError::InvalidResponseWithCode { code, reason: text });
     }
 
-    bar.finish_and_clear();
     Ok(text)
 }
 
 pub async fn upload_temp_file(
     path: impl AsRef<Path> + Send,
+    options: &UploadOptions,
     expiry: LitterExpiry,
-) -> Result<String, UploadFileError> {
-    let client = create_spoof_client(None)?;
-    upload_file(path, UploadTarget::Litterbox { expiry }, &client).await
+    context: &Context,
+) -> Result<Uploaded, UploadFileError> {
+    upload_file(path, options, UploadTarget::Litterbox { expiry }, context).await
+}
+
+pub async fn upload_temp_data(
+    file_name: String,
+    bytes: Bytes,
+    options: &UploadOptions,
+    expiry: LitterExpiry,
+    context: &Context,
+) -> Result<Uploaded, UploadFileError> {
+    upload_data(
+        file_name,
+        bytes,
+        options,
+        UploadTarget::Litterbox { expiry },
+        context,
+    )
+    .await
+}
+
+pub async fn upload_temp_directory(
+    path: impl AsRef<Path> + Send,
+    options: &UploadOptions,
+    expiry: LitterExpiry,
+    context: &Context,
+) -> Result<Uploaded, UploadFileError> {
+    upload_directory(path, options, UploadTarget::Litterbox { expiry }, context).await
 }
@@ -1,7 +1,9 @@
-use indicatif::ProgressBar;
+use bytes::Bytes;
+use chrono::NaiveDateTime;
+use futures_util::{stream, Stream, StreamExt, TryStreamExt};
 use reqwest::Url;
 
-use std::{path::Path, time::Duration};
+use std::{ops::RangeInclusive, path::Path, sync::Arc};
 use tokio::sync::OnceCell;
 
 use tl::ParserOptions;
@@ -9,49 +11,55 @@ use tl::ParserOptions;
 use crate::{
     album::Album,
     authentication::AuthenticatedClient,
-    ensure, get_password_entry, get_username_entry,
-    upload::{upload_file, UploadTarget},
+    client::Context,
+    ensure,
+    file::{parse_size, UploadedFile, DATE_FORMAT},
+    progress::{ProgressReporter, Task, TaskKind},
+    upload::{
+        upload_data, upload_directory, upload_file, upload_stream, UploadOptions, UploadTarget,
+        Uploaded,
+    },
 };
 
 use crate::errors::*;
 
+/// How many pages of the view page are fetched at once.
+const PAGE_CONCURRENCY: usize = 4;
+
 #[derive(Clone)]
 pub struct User {
     client: AuthenticatedClient,
     user_hash: OnceCell<String>,
 }
 
-pub const API_URL: &str = "https://catbox.moe/user/api.php";
-
 impl User {
-    /// Creates a new `User` instance.
+    /// Creates a new `User` instance by logging in.
     ///
     /// The `User` struct stores the authenticated client that provides functions.
+    /// Usually obtained through `CatboxClient::user`, which logs in once and caches the result.
     ///
     /// # Example
     ///
     /// ```
-    /// let user = User::new("kyle", "some_password");
+    /// let user = User::new(client.context().clone(), "kyle".into(), "some_password".into()).await?;
     /// ```
-    pub async fn new() -> Result<Self, UserError> {
-        let username = get_username_entry()?
-            .get_password()
-            .map_err(KeyringError::LackOfUser)?;
-        let password = get_password_entry()?
-            .get_password()
-            .map_err(KeyringError::LackOfUser)?;
-
-        let progress = ProgressBar::new_spinner();
-
-        progress.enable_steady_tick(Duration::from_millis(200));
-
-        progress.set_message("Initilizing user...");
-
-        let client = AuthenticatedClient::new(&username, &password)
-            .await
-            .map_err(|source| UserError::AuthenticatedClientCreation { source, username })?;
+    pub async fn new(
+        context: Context,
+        username: String,
+        password: String,
+    ) -> Result<Self, UserError> {
+        let task = Task::start(
+            context.progress(),
+            TaskKind::Other,
+            "Initilizing user...",
+            None,
+        );
 
-        progress.finish_and_clear();
+        let client = task.complete(
+            AuthenticatedClient::new(context, &username, &password)
+                .await
+                .map_err(|source| UserError::AuthenticatedClientCreation { source, username }),
+        )?;
 
         Ok(Self {
             client,
@@ -59,75 +67,248 @@ impl User {
         })
     }
 
+    /// The same logged in `User`, reporting progress to `progress` instead.
+    #[must_use]
+    pub fn with_progress(&self, progress: Arc<dyn ProgressReporter>) -> Self {
+        Self {
+            client: self.client.with_progress(progress),
+            user_hash: self.user_hash.clone(),
+        }
+    }
+
     /// Uploads the file using `User`.
     ///
     /// # Example
     ///
     /// ```
-    /// let user = User::new("kyle", "some_password");
-    /// user.upload_file("./happy.mp4").await?;
+    /// let user = client.user().await?;
+    /// user.upload_file("./happy.mp4", &UploadOptions::default()).await?;
     /// ```
+    pub async fn upload_file(
+        &self,
+        path: impl AsRef<Path> + Send,
+        options: &UploadOptions,
+    ) -> Result<Uploaded, UserError> {
+        let target = UploadTarget::Catbox {
+            user_hash: self.get_user_hash().await?,
+        };
+        upload_file(path, options, target, &self.client)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Uploads a directory as a single archive using `User`.
     ///
-    /// # Panics
+    /// # Example
     ///
-    /// Panics when the template provided to `ProgressBar` is invalid(compile time mistake)
-    pub async fn upload_file(&self, path: impl AsRef<Path> + Send) -> Result<String, UserError> {
+    /// ```
+    /// let user = client.user().await?;
+    /// user.upload_directory("./screenshots", &UploadOptions::default()).await?;
+    /// ```
+    pub async fn upload_directory(
+        &self,
+        path: impl AsRef<Path> + Send,
+        options: &UploadOptions,
+    ) -> Result<Uploaded, UserError> {
+        let target = UploadTarget::Catbox {
+            user_hash: self.get_user_hash().await?,
+        };
+        upload_directory(path, options, target, &self.client)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Uploads in-memory data as a file named `file_name` using `User`.
+    pub async fn upload_data(
+        &self,
+        file_name: String,
+        bytes: Bytes,
+        options: &UploadOptions,
+    ) -> Result<Uploaded, UserError> {
+        let target = UploadTarget::Catbox {
+            user_hash: self.get_user_hash().await?,
+        };
+        upload_data(file_name, bytes, options, target, &self.client)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Uploads a stream of unknown length as a file named `file_name` using `User`.
+    pub async fn upload_stream<S>(
+        &self,
+        file_name: String,
+        stream: S,
+    ) -> Result<Uploaded, UserError>
+    where
+        S: Stream<Item = Result<Bytes, std::io::Error>> + Send + 'static,
+    {
         let target = UploadTarget::Catbox {
             user_hash: self.get_user_hash().await?,
         };
-        upload_file(path, target, &self.client)
+        upload_stream(file_name, stream, target, &self.client)
             .await
             .map_err(Into::into)
     }
 
+    /// Lets catbox download `url` into the account, returning the url of the new file.
+    pub async fn upload_url(&self, url: &Url) -> Result<String, UserError> {
+        self.api_request(&[("reqtype", "urlupload"), ("url", url.as_str())])
+            .await
+    }
+
+    /// Adds a file of the account to `album`, checking that the account has a file `slug` first.
     pub async fn upload_to_album(&self, album: &Album, slug: &str) -> Result<(), UserError> {
-        let user_hash = self.get_user_hash().await?;
+        let task = Task::start(
+            self.client.progress(),
+            TaskKind::Other,
+            format!("Uploading '{slug}' to album"),
+            None,
+        );
 
-        let short = album
-            .url
-            .path_segments()
-            .ok_or(UserError::ShortParsing {
-                url: album.url.clone(),
-            })?
-            .nth(1)
+        task.complete(
+            async {
+                ensure!(
+                    self.fetch_uploaded_files().await?.into_iter().any(|x| self
+                        .client
+                        .endpoints()
+                        .file_slug(&x.url)
+                        == Some(slug)),
+                    UserError::InvalidSlug {
+                        slug: slug.to_string()
+                    }
+                );
+
+                self.add_to_album(album, &[slug]).await
+            }
+            .await,
+        )
+    }
+
+    /// Adds files to `album` without checking they belong to the account.
+    pub async fn add_to_album(&self, album: &Album, slugs: &[&str]) -> Result<(), UserError> {
+        let short = self.album_short(album)?;
+        let files = slugs.join(" ");
+
+        self.api_request(&[
+            ("reqtype", "addtoalbum"),
+            ("short", short),
+            ("files", &files),
+        ])
+        .await?;
+
+        Ok(())
+    }
+
+    /// Creates a new album containing `slugs`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let user = client.user().await?;
+    /// let album = user.create_album("reactions", "", &["w0v6bk.webm"]).await?;
+    /// ```
+    pub async fn create_album(
+        &self,
+        title: &str,
+        description: &str,
+        slugs: &[&str],
+    ) -> Result<Album, UserError> {
+        let files = slugs.join(" ");
+        let url = self
+            .api_request(&[
+                ("reqtype", "createalbum"),
+                ("title", title),
+                ("desc", description),
+                ("files", &files),
+            ])
+            .await?;
+
+        let url = url.trim();
+        let url = Url::parse(url).map_err(|source| UserError::InvalidAlbumUrl {
+            source,
+            url: url.to_owned(),
+        })?;
+
+        Ok(Album {
+            title: Some(title.to_owned()),
+            description: Some(description.to_owned()),
+            file_count: Some(slugs.len()),
+            ..Album::new(url)
+        })
+    }
+
+    pub async fn remove_from_album(&self, album: &Album, slugs: &[&str]) -> Result<(), UserError> {
+        let short = self.album_short(album)?;
+        let files = slugs.join(" ");
+
+        self.api_request(&[
+            ("reqtype", "removefromalbum"),
+            ("short", short),
+            ("files", &files),
+        ])
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_album(&self, album: &Album) -> Result<(), UserError> {
+        let short = self.album_short(album)?;
+
+        self.api_request(&[("reqtype", "deletealbum"), ("short", short)])
+            .await?;
+
+        Ok(())
+    }
+
+    /// Deletes files from the account, they are removed from every album as well.
+    pub async fn delete_files(&self, slugs: &[&str]) -> Result<(), UserError> {
+        let files = slugs.join(" ");
+
+        self.api_request(&[("reqtype", "deletefiles"), ("files", &files)])
+            .await?;
+
+        Ok(())
+    }
+
+    fn album_short<'a>(&self, album: &'a Album) -> Result<&'a str, UserError> {
+        self.client
+            .endpoints()
+            .album_short(&album.url)
             .ok_or(UserError::ShortParsing {
                 url: album.url.clone(),
-            })?;
-
-        ensure!(
-            self.fetch_uploaded_files()
-                .await?
-                .into_iter()
-                .any(|x| &x.path()[1..] == slug),
-            UserError::InvalidSlug {
-                slug: slug.to_string()
-            }
-        );
+            })
+    }
+
+    /// Sends a form to the user api with the userhash added, returning the response text.
+    ///
+    /// Meant for requests without a method of their own, e.g. ones forwarded by `cbx serve`.
+    pub async fn api_request(&self, form: &[(&str, &str)]) -> Result<String, UserError> {
+        let user_hash = self.get_user_hash().await?;
 
         let resp = self
             .client
-            .post(API_URL)
-            .form(&[
-                ("reqtype", "addtoalbum"),
-                ("userhash", &user_hash),
-                ("short", short),
-                ("files", slug),
-            ])
+            .http()
+            .post(self.client.endpoints().api())
+            .form(&[form, &[("userhash", &user_hash)]].concat())
             .send()
             .await
             .map_err(NetworkError::DownloadRequest)?;
 
         let code = resp.status();
 
-        ensure!(
-            code.is_success(),
-            UserError::InvalidResponseWithCode {
-                code,
-                reason: resp.text().await.map_err(NetworkError::InvalidText)?,
-            }
-        );
+        let text = resp.text().await.map_err(NetworkError::InvalidText)?;
 
-        Ok(())
+        if !code.is_success() {
+            let reqtype = form.iter().find(|(key, _)| *key == "reqtype");
+            return Err(match reqtype {
+                Some((_, x)) if x.ends_with("album") => {
+                    UserError::AlbumRequest { code, reason: text }
+                }
+                _ => UserError::ApiRequest { code, reason: text },
+            });
+        }
+
+        Ok(text)
     }
 
     /// Gets the user hash of a `User`.
@@ -135,13 +316,15 @@ impl User {
     /// # Example
     ///
     /// ```
-    /// let user = User::new("kyle", "some_password");
+    /// let hash = client.user().await?.get_user_hash().await?;
     /// ```
     pub async fn get_user_hash(&self) -> Result<String, UserError> {
-        const ACCOUNT_URL: &str = "https://catbox.moe/user/manage.php";
         self.user_hash
             .get_or_try_init(move || async move {
-                let html = self.client.fetch_html(ACCOUNT_URL).await?;
+                let html = self
+                    .client
+                    .fetch_html(self.client.endpoints().account())
+                    .await?;
                 let html = tl::parse(&html, ParserOptions::default())
                     .map_err(HtmlParsingError::InvalidHtml)?;
                 let parser = html.parser();
@@ -176,48 +359,131 @@ impl User {
     /// # Example
     ///
     /// ```
-    /// let user = User::new("kyle", "some_password");
-    /// let albums = user.fetch_albums(&self).await?;
+    /// let albums = client.user().await?.fetch_albums().await?;
     /// ```
     pub async fn fetch_albums(&self) -> Result<Vec<Album>, UserError> {
-        const ALBUM_VIEW_URL: &str = "https://catbox.moe/user/manage_albums.php";
-
-        let html = self.client.fetch_html(ALBUM_VIEW_URL).await?;
+        let html = self
+            .client
+            .fetch_html(self.client.endpoints().albums())
+            .await?;
         let html =
             tl::parse(&html, ParserOptions::default()).map_err(HtmlParsingError::InvalidHtml)?;
         let parser = html.parser();
 
-        let Some(texts) = html.query_selector("span.textHolder") else {
-            return Ok(vec![]);
-        };
+        let mut albums = Vec::<Album>::new();
+        let mut holder_text = None;
+
+        // every album url is followed by the texts describing it, which are told apart by what
+        // they look like, as the page marks up nothing but the url
+        for node in html.nodes() {
+            if let Some(tag) = node
+                .as_tag()
+                .filter(|x| x.attributes().is_class_member("textHolder"))
+            {
+                let text = tag.inner_text(parser).trim().to_owned();
+                if let Ok(url) = Url::parse(&text) {
+                    albums.push(Album::new(url));
+                    holder_text = Some(text);
+                }
+                continue;
+            }
 
-        let albums = texts
-            .filter_map(|x| x.get(parser))
-            .map(|x| x.inner_text(parser))
-            .filter_map(|x| Url::parse(&x).ok())
-            .map(Album::new)
-            .collect();
+            let (Some(album), Some(text)) = (albums.last_mut(), node.as_raw()) else {
+                continue;
+            };
+            let text = text.as_utf8_str();
+            let text = text.trim();
+            if text.is_empty() || holder_text.take().as_deref() == Some(text) {
+                continue;
+            }
+            describe_album(album, text);
+        }
 
         Ok(albums)
     }
 
-    /// Lists the files created by a `User`.
+    /// Lists the files created by a `User`, from every page of the view page.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let files = client.user().await?.fetch_uploaded_files().await?;
+    /// ```
+    pub async fn fetch_uploaded_files(&self) -> Result<Vec<UploadedFile>, UserError> {
+        self.uploaded_files(1..=u32::MAX).try_collect().await
+    }
+
+    /// Streams the files on pages `pages` of the view page, in the order they are shown.
+    ///
+    /// The pager may only link the pages near the current one, so pages are fetched a few at a
+    /// time, concurrently, until the last one linked has been fetched or a page comes back empty.
     ///
     /// # Example
     ///
     /// ```
-    /// let user = User::new("kyle", "some_password");
-    /// let albums = user.fetch_uploaded_files(&self).await?;
+    /// let user = client.user().await?;
+    /// let mut files = pin!(user.uploaded_files(1..=u32::MAX));
+    /// while let Some(file) = files.try_next().await? {
+    ///     println!("{}", file.url);
+    /// }
     /// ```
-    pub async fn fetch_uploaded_files(&self) -> Result<Vec<Url>, UserError> {
-        const USER_VIEW_URL: &str = "https://catbox.moe/user/view.php";
+    pub fn uploaded_files(
+        &self,
+        pages: RangeInclusive<u32>,
+    ) -> impl Stream<Item = Result<UploadedFile, UserError>> + Send + 'static {
+        let user = self.clone();
+        let (first, last) = pages.into_inner();
+
+        // the next page to fetch, and the highest page known to exist
+        stream::try_unfold((first, first), move |(next, known)| {
+            let user = user.clone();
+            async move {
+                let end = known
+                    .min(last)
+                    .min(next.saturating_add(PAGE_CONCURRENCY as u32 - 1));
+                if next > end {
+                    return Ok(None);
+                }
+
+                let fetched = stream::iter(next..=end)
+                    .map(|page| user.fetch_uploaded_files_page(page))
+                    .buffered(PAGE_CONCURRENCY)
+                    .try_collect::<Vec<_>>()
+                    .await?;
+
+                let exhausted = fetched.iter().any(|(files, _)| files.is_empty());
+                let known = fetched.iter().map(|(_, x)| *x).fold(known, u32::max);
+                let files = fetched.into_iter().flat_map(|(files, _)| files);
+                let state = if exhausted || end == u32::MAX {
+                    (1, 0)
+                } else {
+                    (end + 1, known)
+                };
+                Ok::<_, UserError>(Some((stream::iter(files.map(Ok)), state)))
+            }
+        })
+        .try_flatten()
+    }
 
-        let html = self.client.fetch_html(USER_VIEW_URL).await?;
+    /// Fetches a single page of the view page, along with the highest page its pager links to.
+    async fn fetch_uploaded_files_page(
+        &self,
+        page: u32,
+    ) -> Result<(Vec<UploadedFile>, u32), UserError> {
+        let html = self
+            .client
+            .fetch_html(self.client.endpoints().uploaded_files_page(page))
+            .await?;
         let html =
             tl::parse(&html, ParserOptions::default()).map_err(HtmlParsingError::InvalidHtml)?;
         let parser = html.parser();
 
-        let files = html
+        let mut files = Vec::<UploadedFile>::new();
+        let mut link_text = None;
+
+        // every file link is followed by the texts describing it, which are told apart by what
+        // they look like rather than by markup
+        for node in html
             .get_element_by_id("results")
             .ok_or(HtmlParsingError::LackOfContainer)?
             .get(parser)
@@ -225,12 +491,79 @@ impl User {
             .children()
             .ok_or(HtmlParsingError::LackOfChildren)?
             .all(parser)
+        {
+            if let Some(tag) = node.as_tag().filter(|x| x.attributes().contains("target")) {
+                if let Some(url) = tag
+                    .attributes()
+                    .get("href")
+                    .flatten()
+                    .and_then(|x| x.try_as_utf8_str())
+                    .and_then(|x| Url::parse(x).ok())
+                {
+                    files.push(UploadedFile::new(url));
+                    link_text = Some(tag.inner_text(parser).trim().to_owned());
+                }
+                continue;
+            }
+
+            let (Some(file), Some(text)) = (files.last_mut(), node.as_raw()) else {
+                continue;
+            };
+            let text = text.as_utf8_str();
+            let text = text.trim();
+            if text.is_empty() || link_text.take().as_deref() == Some(text) {
+                continue;
+            }
+            describe_file(file, text);
+        }
+
+        let page_count = html
+            .nodes()
             .iter()
             .filter_map(|x| x.as_tag())
-            .filter(|x| x.attributes().contains("target"))
             .filter_map(|x| x.attributes().get("href")??.try_as_utf8_str())
-            .filter_map(|x| Url::parse(x).ok())
-            .collect();
-        Ok(files)
+            .filter_map(|x| self.client.endpoints().uploaded_files().join(x).ok())
+            .filter(|x| x.path() == self.client.endpoints().uploaded_files().path())
+            .filter_map(|x| {
+                x.query_pairs()
+                    .find(|(key, _)| key == "page")
+                    .and_then(|(_, value)| value.parse::<u32>().ok())
+            })
+            .fold(page, u32::max);
+
+        Ok((files, page_count))
+    }
+}
+
+/// Fills in the field of `file` that `text` looks like, the first text that is neither a date
+/// nor a size being the name.
+fn describe_file(file: &mut UploadedFile, text: &str) {
+    if let Ok(date) = NaiveDateTime::parse_from_str(text, DATE_FORMAT) {
+        file.date.get_or_insert(date);
+    } else if let Some(size) =
+        parse_size(text).filter(|_| file.size.is_none() && text.contains(char::is_alphabetic))
+    {
+        file.size = Some(size);
+    } else if file.name.is_none() {
+        file.name = Some(text.to_owned());
+    }
+}
+
+/// Fills in the field of `album` that `text` looks like, the first text that is neither a date
+/// nor a file count being the title, and the second the description.
+fn describe_album(album: &mut Album, text: &str) {
+    let count = text
+        .strip_suffix("files")
+        .or_else(|| text.strip_suffix("file"))
+        .and_then(|x| x.trim().parse().ok());
+
+    if let Ok(date) = NaiveDateTime::parse_from_str(text, DATE_FORMAT) {
+        album.created.get_or_insert(date);
+    } else if let Some(count) = count {
+        album.file_count.get_or_insert(count);
+    } else if album.title.is_none() {
+        album.title = Some(text.to_owned());
+    } else if album.description.is_none() {
+        album.description = Some(text.to_owned());
     }
 }
@@ -0,0 +1,212 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use catbox::{
+    album::Album,
+    archive::Excludes,
+    history::{History, HistoryEntry},
+    upload::{LitterExpiry, UploadOptions, Uploaded},
+    AppError, CatboxClient, UploadFileError,
+};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use notify::{
+    event::{CreateKind, ModifyKind},
+    EventKind, RecursiveMode, Watcher,
+};
+use tokio::sync::mpsc;
+
+use crate::{events::OutputFormat, report_upload};
+
+/// Where `cbx watch` sends the files.
+pub enum Destination {
+    Catbox { album: Option<Album> },
+    Litterbox { expiry: LitterExpiry },
+}
+
+/// Which files of the watched directory are uploaded, matched against the relative path.
+pub struct WatchRules {
+    /// every file is wanted when empty
+    pub includes: GlobSet,
+    pub excludes: Excludes,
+    /// how long a file has to stay untouched before it counts as finished
+    pub debounce: Duration,
+    pub recursive: bool,
+}
+
+impl WatchRules {
+    pub fn new(
+        include: &[String],
+        exclude: &[String],
+        debounce: Duration,
+        recursive: bool,
+    ) -> Result<Self, UploadFileError> {
+        let mut includes = GlobSetBuilder::new();
+        for pattern in include {
+            includes.add(
+                Glob::new(pattern).map_err(|source| UploadFileError::InvalidGlob {
+                    source,
+                    pattern: pattern.clone(),
+                })?,
+            );
+        }
+        let includes = includes
+            .build()
+            .map_err(|source| UploadFileError::InvalidGlob {
+                source,
+                pattern: String::new(),
+            })?;
+
+        Ok(Self {
+            includes,
+            excludes: Excludes::new(exclude)?,
+            debounce,
+            recursive,
+        })
+    }
+
+    fn wants(&self, relative: &Path) -> bool {
+        (self.includes.is_empty() || self.includes.is_match(relative))
+            && !self.excludes.is_excluded(relative)
+    }
+}
+
+/// Whether an event can mean a file was written or moved into place.
+const fn is_write(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Create(CreateKind::File | CreateKind::Any)
+            | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any)
+    )
+}
+
+/// Uploads files written to `dir` until the process is interrupted.
+///
+/// A file is uploaded once no event touched it for `rules.debounce`, so half written files are
+/// skipped. Failed uploads are reported and don't stop the watch, every upload is recorded in
+/// `history`.
+pub async fn watch(
+    client: &CatboxClient,
+    dir: &Path,
+    rules: &WatchRules,
+    destination: &Destination,
+    options: &UploadOptions,
+    history: &History,
+    output: OutputFormat,
+) -> Result<(), AppError> {
+    let watch_error = |source| AppError::Watch {
+        source,
+        dir: dir.to_path_buf(),
+    };
+    // events carry absolute paths, so the globs need an absolute root to strip
+    let dir = dir
+        .canonicalize()
+        .map_err(|source| UploadFileError::ReadFile {
+            source,
+            file: dir.to_path_buf(),
+        })?;
+
+    let (sender, mut events) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = sender.send(event);
+    })
+    .map_err(watch_error)?;
+    let mode = if rules.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher.watch(&dir, mode).map_err(watch_error)?;
+
+    // last event of every file that isn't uploaded yet
+    let mut pending = HashMap::<PathBuf, Instant>::new();
+    // length and modification time of every uploaded file, to skip events that changed nothing
+    let mut uploaded = HashMap::<PathBuf, (u64, Option<SystemTime>)>::new();
+    let mut tick = tokio::time::interval((rules.debounce / 4).max(Duration::from_millis(50)));
+
+    loop {
+        tokio::select! {
+            Some(event) = events.recv() => match event {
+                Ok(event) if is_write(&event.kind) => {
+                    for path in event.paths {
+                        if path.strip_prefix(&dir).is_ok_and(|x| rules.wants(x)) {
+                            pending.insert(path, Instant::now());
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(error) => eprintln!("Error: {}", watch_error(error)),
+            },
+            _ = tick.tick() => {
+                let finished = pending
+                    .iter()
+                    .filter(|(_, x)| x.elapsed() >= rules.debounce)
+                    .map(|(x, _)| x.clone())
+                    .collect::<Vec<_>>();
+
+                for path in finished {
+                    pending.remove(&path);
+                    let Ok(metadata) = std::fs::metadata(&path) else {
+                        continue;
+                    };
+                    let version = (metadata.len(), metadata.modified().ok());
+                    if !metadata.is_file() || uploaded.get(&path) == Some(&version) {
+                        continue;
+                    }
+
+                    match upload(client, &path, destination, options, history, output).await {
+                        Ok(()) => {
+                            uploaded.insert(path, version);
+                        }
+                        Err(error) => eprintln!("Error: {error}"),
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn upload(
+    client: &CatboxClient,
+    path: &Path,
+    destination: &Destination,
+    options: &UploadOptions,
+    history: &History,
+    output: OutputFormat,
+) -> Result<(), AppError> {
+    // the upload is recorded before anything else can fail, so it is never lost from the history
+    let (uploaded, added) = match destination {
+        Destination::Catbox { album } => {
+            let uploaded = client.upload(path, options).await?;
+            let mut entry = entry(path, &uploaded);
+
+            let added = match album {
+                Some(album) => {
+                    let slug = client.file_slug(&uploaded.url);
+                    let added = client.add_to_album(album, &slug).await;
+                    if added.is_ok() {
+                        entry = entry.album(album.url.clone());
+                    }
+                    added
+                }
+                None => Ok(()),
+            };
+            history.append(&entry)?;
+            (uploaded, added)
+        }
+        Destination::Litterbox { expiry } => {
+            let uploaded = client.upload_temp(path, options, *expiry).await?;
+            history.append(&entry(path, &uploaded).litterbox(*expiry))?;
+            (uploaded, Ok(()))
+        }
+    };
+
+    report_upload(path, &uploaded, output, None)?;
+    Ok(added?)
+}
+
+fn entry(path: &Path, uploaded: &Uploaded) -> HistoryEntry {
+    HistoryEntry::new("watch", Some(path.to_path_buf()), uploaded)
+}
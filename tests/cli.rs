@@ -0,0 +1,1279 @@
+mod common;
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read},
+    process::Stdio,
+    time::{Duration, Instant},
+};
+
+use common::MockServer;
+
+fn stdout_json(output: &std::process::Output) -> serde_json::Value {
+    serde_json::from_slice(&output.stdout).expect("stdout is json")
+}
+
+/// A PNG chunk, the crc isn't checked by anything involved so it is left zeroed.
+fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&[0; 4]);
+    chunk
+}
+
+#[test]
+fn litterbox_upload_is_anonymous() {
+    let server = MockServer::start();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("note.txt");
+    fs::write(&path, "temporary").unwrap();
+
+    server
+        .cbx()
+        .env_remove("CBX_USERNAME")
+        .env_remove("CBX_PASSWORD")
+        .args(["file", "upload", "--use-litterbox", "--expiry", "12h"])
+        .arg(&path)
+        .status()
+        .unwrap()
+        .success()
+        .then_some(())
+        .expect("litterbox upload succeeds");
+
+    let state = server.state();
+    assert!(state.files.is_empty());
+    assert_eq!(state.litter.len(), 1);
+    assert_eq!(state.litter[0].name, "note.txt");
+    assert_eq!(state.litter[0].bytes, b"temporary");
+    assert_eq!(state.litter[0].expiry.as_deref(), Some("12h"));
+}
+
+#[test]
+fn upload_then_list_files() {
+    let server = MockServer::start();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cat.png");
+    fs::write(&path, b"not really a png").unwrap();
+
+    server.run(&["file", "upload", path.to_str().unwrap()]);
+
+    let url = {
+        let state = server.state();
+        assert_eq!(state.files.len(), 1);
+        assert_eq!(state.files[0].bytes, b"not really a png");
+        state.file_url(&state.files[0].slug)
+    };
+
+    let listed = stdout_json(&server.run(&["--json", "file", "list"]));
+    assert_eq!(
+        listed,
+        serde_json::json!([{
+            "url": url,
+            "name": "cat.png",
+            "size": 16,
+            "date": "2024-01-02T12:00:00",
+            "short": "000001",
+            "extension": "png",
+        }])
+    );
+}
+
+#[test]
+fn long_file_list_shows_metadata() {
+    let server = MockServer::start();
+    server.seed_file("first.png", b"1");
+    server.seed_file("second.mp4", &[0; 2048]);
+
+    let output = server.run(&["file", "list", "--long"]);
+    let lines = String::from_utf8(output.stdout).unwrap();
+    let lines = lines.lines().collect::<Vec<_>>();
+
+    assert_eq!(
+        lines,
+        [
+            "DATE                   SIZE  FILE        NAME",
+            "2024-01-03 12:00:00  2.0 KB  000002.mp4  second.mp4",
+            "2024-01-02 12:00:00     1 B  000001.png  first.png",
+        ]
+    );
+}
+
+#[test]
+fn jsonl_output_reports_upload_events() {
+    let server = MockServer::start();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("hello.txt");
+    fs::write(&path, "hello").unwrap();
+
+    let output = server.run(&[
+        "--output",
+        "jsonl",
+        "file",
+        "upload",
+        path.to_str().unwrap(),
+    ]);
+
+    let events = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|x| serde_json::from_str::<serde_json::Value>(x).unwrap())
+        .collect::<Vec<_>>();
+    let path = path.to_str().unwrap();
+    let url = {
+        let state = server.state();
+        state.file_url(&state.files[0].slug)
+    };
+
+    assert_eq!(
+        events,
+        [
+            serde_json::json!({"event": "upload_started", "path": path, "total": 5}),
+            serde_json::json!({"event": "progress", "path": path, "sent": 5, "total": 5}),
+            serde_json::json!({
+                "event": "upload_finished",
+                "path": path,
+                "url": url,
+                "size": 5,
+                "sha256": "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+            }),
+        ]
+    );
+
+    // downloads are not uploads
+    let short = server.create_album("abc123", "pulled");
+    server.state().albums.get_mut(&short).unwrap().files =
+        vec![url.rsplit('/').next().unwrap().to_owned()];
+    let target = dir.path().join("pulled");
+    let output = server.run(&[
+        "--output",
+        "jsonl",
+        "album",
+        "pull",
+        "--album",
+        &short,
+        target.to_str().unwrap(),
+    ]);
+    assert!(target.join(url.rsplit('/').next().unwrap()).exists());
+    assert!(!String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("\"event\""));
+}
+
+#[test]
+fn jsonl_output_reports_errors() {
+    let server = MockServer::start();
+
+    let output = server
+        .cbx()
+        .args(["--output", "jsonl", "file", "upload", "missing.txt"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let last = String::from_utf8(output.stdout).unwrap();
+    let event: serde_json::Value = serde_json::from_str(last.lines().last().unwrap()).unwrap();
+    assert_eq!(event["event"], "error");
+    assert!(event["message"].as_str().unwrap().contains("missing.txt"));
+
+    // an upload catbox rejects is reported once, as the error of that upload
+    server.state().reject_uploads = true;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("note.txt");
+    fs::write(&path, b"note").unwrap();
+    let output = server
+        .cbx()
+        .args([
+            "--output",
+            "jsonl",
+            "file",
+            "upload",
+            path.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(8));
+    let errors = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|x| serde_json::from_str::<serde_json::Value>(x).unwrap())
+        .filter(|x| x["event"] == "error")
+        .collect::<Vec<_>>();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0]["path"], path.to_str().unwrap());
+}
+
+#[test]
+fn file_list_follows_pages() {
+    let server = MockServer::start();
+    server.state().page_size = Some(2);
+    let urls = (0..5)
+        .map(|x| server.seed_file(&format!("{x}.txt"), b"x"))
+        .collect::<Vec<_>>();
+
+    let list = |args: &[&str]| {
+        let mut command = vec!["--json", "file", "list"];
+        command.extend_from_slice(args);
+        stdout_json(&server.run(&command))
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| x["url"].as_str().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(list(&[]), urls);
+    assert_eq!(list(&["--page", "2"]), urls[2..4]);
+    assert_eq!(list(&["--limit", "3"]), urls[..3]);
+
+    // files past the first page can still be added to albums
+    let short = server.create_album("abc123", "reactions");
+    server.run(&["album", "add", "--album", &short, &urls[4]]);
+    assert_eq!(server.state().albums[&short].files.len(), 1);
+}
+
+#[test]
+fn file_list_follows_windowed_pagers() {
+    let server = MockServer::start();
+    server.state().page_size = Some(1);
+    // the first page only links to the second
+    server.state().page_window = Some(1);
+    let urls = (0..9)
+        .map(|x| server.seed_file(&format!("{x}.txt"), b"x"))
+        .collect::<Vec<_>>();
+
+    let listed = stdout_json(&server.run(&["--json", "file", "list"]))
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| x["url"].as_str().unwrap().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(listed, urls);
+}
+
+#[test]
+fn listings_are_cached() {
+    let server = MockServer::start();
+    let first = server.seed_file("a.txt", b"a");
+
+    let list = |args: &[&str]| {
+        let mut command = vec!["--json"];
+        command.extend_from_slice(args);
+        command.extend_from_slice(&["file", "list"]);
+        stdout_json(&server.run(&command))
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| x["url"].as_str().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(list(&[]), [first.as_str()]);
+
+    // changes made behind the back of cbx only show up once the listing is fetched again
+    let second = server.seed_file("b.txt", b"b");
+    assert_eq!(list(&[]), [first.as_str()]);
+    assert_eq!(list(&["--refresh"]), [first.as_str(), second.as_str()]);
+
+    // files missing from the cache are fetched before they are rejected
+    let third = server.seed_file("c.txt", b"c");
+    let short = server.create_album("abc123", "reactions");
+    server.run(&["album", "add", "--album", &short, &third]);
+    assert_eq!(list(&[]), [first.as_str(), second.as_str(), third.as_str()]);
+
+    // uploads through cbx are added to the cached files, without fetching them again
+    server.seed_file("hidden.txt", b"hidden");
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("d.txt");
+    fs::write(&path, b"d").unwrap();
+    server.run(&["file", "upload", "--album", &short, path.to_str().unwrap()]);
+    let fourth = server.state().files.last().unwrap().slug.clone();
+    let listed = list(&[]);
+    assert_eq!(listed.len(), 4);
+    assert!(listed[3].ends_with(&fourth));
+    assert_eq!(server.state().albums[&short].files.last(), Some(&fourth));
+
+    server.run(&["config", "set", "listing-ttl", "0"]);
+    server.state().files.clear();
+    assert!(list(&[]).is_empty());
+}
+
+#[test]
+fn file_list_filters_and_sorts() {
+    let server = MockServer::start();
+    // uploaded a day apart, starting on 2024-01-02
+    server.seed_file("cat.png", &[0; 300]);
+    server.seed_file("Dog.PNG", &[0; 100]);
+    server.seed_file("clip.mp4", &[0; 200]);
+    server.seed_file("catalog.txt", &[0; 50]);
+
+    let names = |args: &[&str]| {
+        let mut command = vec!["--json", "file", "list"];
+        command.extend_from_slice(args);
+        stdout_json(&server.run(&command))
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| x["name"].as_str().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(names(&["--ext", "png"]), ["cat.png", "Dog.PNG"]);
+    assert_eq!(names(&["--name", "CAT"]), ["cat.png", "catalog.txt"]);
+    assert_eq!(
+        names(&["--name-regex", r"^c.*\.(mp4|txt)$"]),
+        ["clip.mp4", "catalog.txt"]
+    );
+    assert_eq!(
+        names(&["--since", "2024-01-03", "--until", "2024-01-04"]),
+        ["Dog.PNG", "clip.mp4"]
+    );
+    assert_eq!(
+        names(&["--min-size", "100", "--max-size", "200B"]),
+        ["Dog.PNG", "clip.mp4"]
+    );
+    assert_eq!(
+        names(&["--sort", "size"]),
+        ["catalog.txt", "Dog.PNG", "clip.mp4", "cat.png"]
+    );
+    assert_eq!(
+        names(&["--sort", "name", "--reverse", "--limit", "2"]),
+        ["Dog.PNG", "clip.mp4"]
+    );
+
+    let output = server
+        .cbx()
+        .args(["file", "list", "--name", "a", "--name-regex", "b"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn wrong_credentials_fail() {
+    let server = MockServer::start();
+
+    let output = server
+        .cbx()
+        .env("CBX_PASSWORD", "wrong")
+        .args(["file", "list"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(5));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("kyle"));
+}
+
+#[test]
+fn json_errors_are_structured() {
+    let server = MockServer::start();
+
+    let output = server
+        .cbx()
+        .args(["--json", "file", "upload", "missing.txt"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(8));
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["error"]["kind"], "upload_file");
+    assert!(error["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("missing.txt"));
+    assert!(error["error"]["chain"].is_array());
+
+    // catbox rejecting an album request is an album error
+    let url = server.seed_file("cat.png", b"png");
+    let output = server
+        .cbx()
+        .args(["--json", "album", "add", "--album", "missing", &url])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(9));
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["error"]["kind"], "album");
+}
+
+#[test]
+fn broken_config_can_be_repaired() {
+    let server = MockServer::start();
+    fs::write(server.config_path(), "{ not json").unwrap();
+
+    let output = server.cbx().args(["file", "list"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(3));
+
+    let output = server.run(&["config", "show"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("using the defaults for: "));
+    server.run(&["config", "set", "listing-ttl", "60"]);
+    server.run(&["file", "list"]);
+    let config = fs::read_to_string(server.config_path()).unwrap();
+    assert!(config.contains("\"listing-ttl\": 60"));
+
+    // only the settings that can't be parsed are reset
+    fs::write(
+        server.config_path(),
+        r#"{"listing-ttl": "soon", "strip-metadata": true}"#,
+    )
+    .unwrap();
+    let output = server.run(&["config", "set", "tls", "rustls"]);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .trim_end()
+        .ends_with("using the defaults for: listing-ttl"));
+    let config = fs::read_to_string(server.config_path()).unwrap();
+    assert!(config.contains("\"strip-metadata\": true"));
+    assert!(config.contains("\"listing-ttl\": null"));
+
+    // urls that can't be sites are refused, and ones saved anyway can still be unset
+    let output = server
+        .cbx()
+        .args(["config", "set", "files-url", "mailto:x"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    fs::write(server.config_path(), r#"{"files-url": "mailto:x"}"#).unwrap();
+    let output = server.cbx().args(["file", "list"]).output().unwrap();
+    assert!(!output.status.success());
+    server.run(&["config", "set", "files-url", ""]);
+    server.run(&["file", "list"]);
+
+    // so are proxies and CA bundles the http client can't be built with
+    for (key, value) in [("proxy", "http://[bad"), ("ca-bundle", "missing.pem")] {
+        let output = server
+            .cbx()
+            .args(["config", "set", key, value])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(3));
+    }
+    fs::write(server.config_path(), r#"{"ca-bundle": "missing.pem"}"#).unwrap();
+    server.run(&["config", "set", "ca-bundle", ""]);
+    server.run(&["file", "list"]);
+}
+
+#[test]
+fn add_files_to_album() {
+    let server = MockServer::start();
+    let short = server.create_album("abc123", "reactions");
+    let first = server.seed_file("first.png", b"1");
+    let second = server.seed_file("second.mp4", b"2");
+    let second_slug = second.rsplit('/').next().unwrap().to_owned();
+
+    // urls and bare slugs are both accepted
+    server.run(&["album", "add", "--album", &short, &first, &second_slug]);
+
+    let listed = stdout_json(&server.run(&["--json", "album", "list", "--album", &short]));
+    assert_eq!(listed["album"]["title"], "reactions");
+    assert_eq!(listed["album"]["file_count"], 2);
+    let mut urls = listed["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| x["url"].as_str().unwrap().to_owned())
+        .collect::<Vec<_>>();
+    urls.sort();
+    assert_eq!(urls, [first, second]);
+
+    let albums = stdout_json(&server.run(&["--json", "album", "list"]));
+    assert_eq!(
+        albums,
+        serde_json::json!([{
+            "url": server.state().album_url(&short),
+            "title": "reactions",
+            "description": null,
+            "created": "2024-01-01T12:00:00",
+            "file_count": 2,
+        }])
+    );
+
+    let output = server.run(&["album", "list"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        format!("Album 1: reactions ({})", server.state().album_url(&short))
+    );
+
+    // album urls may come without their scheme, and with a trailing slash
+    let third = server.seed_file("third.gif", b"3");
+    let album_url = server.state().album_url(&short);
+    let without_scheme = format!("{}/", album_url.split_once("://").unwrap().1);
+    server.run(&["album", "add", "--album", &without_scheme, &third]);
+    server.run(&["album", "add", "--album", &format!("{album_url}/"), &third]);
+    assert_eq!(server.state().albums[&short].files.len(), 3);
+}
+
+#[test]
+fn album_random_picks_are_seeded_and_filtered() {
+    let server = MockServer::start();
+    let short = server.create_album("abc123", "reactions");
+    let mut images = Vec::new();
+    for i in 0..6 {
+        images.push(server.seed_file(&format!("{i}.png"), b"png"));
+        server.seed_file(&format!("{i}.mp4"), b"mp4");
+    }
+    {
+        let mut state = server.state();
+        let slugs = state.files.iter().map(|x| x.slug.clone()).collect();
+        state.albums.get_mut(&short).unwrap().files = slugs;
+    }
+
+    let pick = |args: &[&str]| {
+        let mut command = vec!["album", "random", "--album", &short];
+        command.extend_from_slice(args);
+        String::from_utf8(server.run(&command).stdout)
+            .unwrap()
+            .lines()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>()
+    };
+
+    let first = pick(&["--count", "3", "--ext", "png", "--seed", "7"]);
+    assert_eq!(first.len(), 3);
+    assert!(first.iter().all(|x| images.contains(x)));
+    assert_eq!(
+        first,
+        pick(&["--count", "3", "--ext", "png", "--seed", "7"])
+    );
+
+    // asking for more than there are returns every match once
+    let mut all = pick(&["--count", "100", "--ext", "png"]);
+    all.sort();
+    images.sort();
+    assert_eq!(all, images);
+
+    let dir = tempfile::tempdir().unwrap();
+    let output = server
+        .cbx()
+        .current_dir(dir.path())
+        .args([
+            "album",
+            "random",
+            "--album",
+            &short,
+            "--ext",
+            "mp4",
+            "--download",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let path = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(fs::read(dir.path().join(path.trim())).unwrap(), b"mp4");
+}
+
+#[test]
+fn album_sync_uploads_changes_and_deletes() {
+    let server = MockServer::start();
+    let short = server.create_album("abc123", "reactions");
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.txt"), b"a").unwrap();
+    fs::write(dir.path().join("b.txt"), b"b").unwrap();
+    fs::write(dir.path().join("c.txt"), b"c").unwrap();
+    let dir_arg = dir.path().to_str().unwrap();
+
+    server.run(&["album", "sync", "--album", &short, dir_arg]);
+    assert_eq!(server.state().albums[&short].files.len(), 3);
+    // uploads run concurrently, so slugs aren't handed out in file order
+    let slug = |name: &str| {
+        let state = server.state();
+        let file = state.files.iter().find(|x| x.name == name).unwrap();
+        file.slug.clone()
+    };
+    let (a, b, c) = (slug("a.txt"), slug("b.txt"), slug("c.txt"));
+
+    // uploads of earlier syncs are compared by the hash in the history, not downloaded again
+    let history = fs::read_to_string(server.history_path()).unwrap();
+    assert_eq!(history.lines().count(), 3);
+    server
+        .state()
+        .files
+        .iter_mut()
+        .find(|x| x.slug == a)
+        .unwrap()
+        .bytes = b"stale".to_vec();
+    fs::write(dir.path().join(".cbx-pull.json"), b"{}").unwrap();
+
+    fs::write(dir.path().join("b.txt"), b"changed").unwrap();
+    fs::remove_file(dir.path().join("c.txt")).unwrap();
+
+    let plan = server.run(&[
+        "album",
+        "sync",
+        "--album",
+        &short,
+        "--delete",
+        "--dry-run",
+        dir_arg,
+    ]);
+    let plan = String::from_utf8(plan.stdout).unwrap();
+    assert_eq!(
+        plan,
+        format!(
+            "replace {} ({b})\nremove  {c} (c.txt)\n1 unchanged\n",
+            dir.path().join("b.txt").display()
+        )
+    );
+    assert_eq!(server.state().files.len(), 3);
+
+    server.run(&["album", "sync", "--album", &short, "--delete", dir_arg]);
+    let state = server.state();
+    assert_eq!(state.files.len(), 4);
+    let mut album = state.albums[&short].files.clone();
+    album.sort();
+    assert_eq!(album, [a, "000004.txt".to_owned()]);
+    assert_eq!(state.file("000004.txt").unwrap().bytes, b"changed");
+}
+
+#[test]
+fn album_pull_downloads_missing_files_once() {
+    let server = MockServer::start();
+    let short = server.create_album("abc123", "reactions");
+    server.seed_file("a.png", b"a");
+    server.seed_file("b.png", b"b");
+    server.state().albums.get_mut(&short).unwrap().files =
+        vec!["000001.png".to_owned(), "000002.png".to_owned()];
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("mirror");
+    fs::create_dir(&target).unwrap();
+    fs::write(target.join("notes.txt"), b"mine").unwrap();
+    let target_arg = target.to_str().unwrap();
+
+    let pull = |args: &[&str]| {
+        let mut command = vec!["--json", "album", "pull", "--album", &short, target_arg];
+        command.extend_from_slice(args);
+        let output = server.run(&command);
+        assert!(output.status.success());
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+    };
+
+    let report = pull(&[]);
+    assert_eq!(report["downloaded"].as_array().unwrap().len(), 2);
+    assert_eq!(fs::read(target.join("000002.png")).unwrap(), b"b");
+
+    let report = pull(&[]);
+    assert_eq!(report["downloaded"], serde_json::json!([]));
+    assert_eq!(report["unchanged"], 2);
+
+    server.state().albums.get_mut(&short).unwrap().files = vec!["000002.png".to_owned()];
+    let report = pull(&["--delete"]);
+    assert_eq!(
+        report["removed"],
+        serde_json::json!([target.join("000001.png").to_str().unwrap()])
+    );
+    assert!(!target.join("000001.png").exists());
+    assert!(target.join("notes.txt").exists());
+
+    // intervals too long to represent are rejected rather than wrapped around
+    let output = server
+        .cbx()
+        .args(["album", "pull", "--album", &short, target_arg])
+        .args(["--watch", "9999999999999999h"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("invalid interval"));
+}
+
+#[test]
+fn watch_uploads_finished_files() {
+    let server = MockServer::start();
+    let short = server.create_album("abc123", "reactions");
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("old.png"), b"old").unwrap();
+
+    let mut watch = server
+        .cbx()
+        .args(["watch", "--album", &short, "--exclude", "*.tmp"])
+        .args(["--debounce", "200ms"])
+        .arg(dir.path())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    // give the watcher time to start
+    std::thread::sleep(Duration::from_millis(500));
+    fs::write(dir.path().join("partial.tmp"), b"tmp").unwrap();
+    fs::write(dir.path().join("shot.png"), b"shot").unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while server.state().albums[&short].files.is_empty() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    std::thread::sleep(Duration::from_millis(500));
+    watch.kill().unwrap();
+    watch.wait().unwrap();
+
+    let state = server.state();
+    assert_eq!(state.files.len(), 1);
+    assert_eq!(state.files[0].name, "shot.png");
+    assert_eq!(state.albums[&short].files, [state.files[0].slug.clone()]);
+
+    let history = fs::read_to_string(server.history_path()).unwrap();
+    let entry = serde_json::from_str::<serde_json::Value>(history.trim()).unwrap();
+    assert_eq!(entry["source"], "watch");
+    assert_eq!(entry["url"], state.file_url(&state.files[0].slug));
+    assert_eq!(entry["album"], state.album_url(&short));
+}
+
+#[test]
+fn watch_records_uploads_the_album_rejects() {
+    let server = MockServer::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut watch = server
+        .cbx()
+        .args(["watch", "--album", "missing", "--debounce", "200ms"])
+        .arg(dir.path())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(500));
+    fs::write(dir.path().join("shot.png"), b"shot").unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !server.history_path().exists() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    watch.kill().unwrap();
+    watch.wait().unwrap();
+
+    let state = server.state();
+    let history = fs::read_to_string(server.history_path()).unwrap();
+    let entry = serde_json::from_str::<serde_json::Value>(history.trim()).unwrap();
+    assert_eq!(entry["url"], state.file_url(&state.files[0].slug));
+    assert!(entry["album"].is_null());
+}
+
+#[test]
+fn links_are_formatted() {
+    let server = MockServer::start();
+    let dir = tempfile::tempdir().unwrap();
+    let image = dir.path().join("cat.png");
+    let notes = dir.path().join("notes.txt");
+    fs::write(&image, b"png").unwrap();
+    fs::write(&notes, b"hello").unwrap();
+
+    let output = server
+        .cbx()
+        .args(["file", "upload", "--format", "markdown"])
+        .args([&image, &notes])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let mut lines = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+    lines.sort();
+    let (image_url, notes_url) = {
+        let state = server.state();
+        let url = |name: &str| {
+            let file = state.files.iter().find(|x| x.name == name).unwrap();
+            state.file_url(&file.slug)
+        };
+        (url("cat.png"), url("notes.txt"))
+    };
+    assert_eq!(
+        lines,
+        [
+            format!("![cat.png]({image_url})"),
+            format!("[notes.txt]({notes_url})"),
+        ]
+    );
+
+    let output = server.run(&["file", "list", "--sort", "name", "--format", "bbcode"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("[img]{image_url}[/img]\n[url={notes_url}]notes.txt[/url]\n")
+    );
+
+    let output = server.run(&[
+        "file",
+        "list",
+        "--ext",
+        "txt",
+        "--template",
+        "{short} {size} {name}",
+    ]);
+    let short = notes_url
+        .rsplit('/')
+        .next()
+        .unwrap()
+        .trim_end_matches(".txt");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{short} 5 B notes.txt\n")
+    );
+
+    // placeholders in file names are printed as they are
+    let tricky = server.seed_file("{url} {size}.md", b"x");
+    let output = server.run(&[
+        "--refresh",
+        "file",
+        "list",
+        "--ext",
+        "md",
+        "--format",
+        "template",
+        "--template",
+        "{name} -> {url}",
+    ]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{{url}} {{size}}.md -> {tricky}\n")
+    );
+
+    // bbcode can't escape, so brackets in names are dropped instead of closing the tag
+    let bracketed = server.seed_file("[/url][b]loud.zip", b"x");
+    let output = server.run(&[
+        "--refresh",
+        "file",
+        "list",
+        "--ext",
+        "zip",
+        "--format",
+        "bbcode",
+    ]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("[url={bracketed}]/urlbloud.zip[/url]\n")
+    );
+}
+
+#[test]
+fn stdin_is_uploaded() {
+    let server = MockServer::start();
+
+    for (args, input) in [
+        (&["--name", "note.txt"][..], &b"from a pipe"[..]),
+        (&[][..], &b"\x89PNG\r\n\x1a\n"[..]),
+    ] {
+        let mut child = server
+            .cbx()
+            .args(["file", "upload", "--format", "url"])
+            .args(args)
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        std::io::Write::write_all(&mut child.stdin.take().unwrap(), input).unwrap();
+        assert!(child.wait_with_output().unwrap().status.success());
+    }
+
+    let state = server.state();
+    let names = state
+        .files
+        .iter()
+        .map(|x| x.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["note.txt", "stdin.png"]);
+}
+
+#[test]
+fn integrations_are_generated() {
+    let server = MockServer::start();
+
+    let uploader = stdout_json(&server.run(&["integrations", "sharex"]));
+    assert_eq!(
+        uploader["RequestURL"],
+        format!("{}/user/api.php", server.url)
+    );
+    assert_eq!(uploader["Arguments"]["userhash"], common::USER_HASH);
+    assert_eq!(uploader["FileFormName"], "fileToUpload");
+
+    let output = server
+        .cbx()
+        .env_remove("CBX_USERNAME")
+        .env_remove("CBX_PASSWORD")
+        .args([
+            "integrations",
+            "sharex",
+            "--use-litterbox",
+            "--expiry",
+            "24h",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let uploader = stdout_json(&output);
+    assert_eq!(uploader["Arguments"]["time"], "24h");
+    assert!(uploader["Arguments"].get("userhash").is_none());
+
+    let output = server.run(&["integrations", "hook", "grim"]);
+    let script = String::from_utf8(output.stdout).unwrap();
+    assert!(script.contains("grim -g"));
+    assert!(script.contains("| cbx file upload --format url"));
+}
+
+#[test]
+fn completions_are_generated() {
+    let server = MockServer::start();
+    server.create_album("pets", "Pets");
+    let url = server.seed_file("cat.png", b"meow");
+    let slug = url.rsplit('/').next().unwrap();
+
+    let script = String::from_utf8(server.run(&["completions", "bash"]).stdout).unwrap();
+    assert!(script.contains("complete -o default -F _cbx cbx"));
+
+    let complete = |words: &[&str]| {
+        let mut args = vec!["completions", "fish", "--complete", "--"];
+        args.extend(words);
+        String::from_utf8(server.run(&args).stdout).unwrap()
+    };
+    assert!(complete(&["album", "a"]).starts_with("add\t"));
+    assert_eq!(
+        complete(&["file", "upload", "--expiry", "1"]),
+        "1h\t\n12h\t\n"
+    );
+
+    // albums and files are only completed from the listing cache, which completing never fetches
+    assert_eq!(complete(&["album", "add", "--album", ""]), "");
+    server.run(&["album", "list"]);
+    server.run(&["file", "list"]);
+    assert_eq!(complete(&["album", "add", "--album", ""]), "pets\tPets\n");
+    assert_eq!(
+        complete(&["album", "add", "--album", "pets", ""]),
+        format!("{slug}\tcat.png\n")
+    );
+
+    // however old the cache is
+    server.run(&["config", "set", "listing-ttl", "0"]);
+    server.create_album("dogs", "Dogs");
+    assert_eq!(complete(&["album", "add", "--album", "d"]), "");
+    server.run(&["album", "list"]);
+    assert_eq!(complete(&["album", "add", "--album", "d"]), "dogs\tDogs\n");
+
+    let page = String::from_utf8(server.run(&["man"]).stdout).unwrap();
+    assert!(page.starts_with(".TH CBX 1"));
+    assert!(page.contains(".SS \"cbx album add \\-\\-album <album> [<files>...]\""));
+}
+
+#[tokio::test]
+async fn serve_forwards_requests() {
+    let server = MockServer::start();
+    let mut gateway = server
+        .cbx()
+        .args(["serve", "--bind", "127.0.0.1:0", "--token", "secret"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(gateway.stderr.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let api = line
+        .trim()
+        .strip_prefix("Listening on ")
+        .unwrap()
+        .to_owned();
+
+    let http = reqwest::Client::builder().no_proxy().build().unwrap();
+    let upload = |token: &str| {
+        let part =
+            reqwest::multipart::Part::bytes(b"through the gateway".to_vec()).file_name("note.txt");
+        let form = reqwest::multipart::Form::new()
+            .text("reqtype", "fileupload")
+            .text("userhash", token.to_owned())
+            .part("fileToUpload", part);
+        http.post(&api).multipart(form).send()
+    };
+
+    let response = upload("wrong").await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let response = upload("secret").await.unwrap();
+    assert!(response.status().is_success());
+    let file_url = response.text().await.unwrap();
+
+    let response = http
+        .post(&api)
+        .bearer_auth("secret")
+        .form(&[
+            ("reqtype", "urlupload"),
+            ("url", "https://example.com/cat.png"),
+        ])
+        .send()
+        .await
+        .unwrap();
+    let remote_url = response.text().await.unwrap();
+
+    let slug = file_url.rsplit('/').next().unwrap().to_owned();
+    let response = http
+        .post(&api)
+        .form(&[
+            ("reqtype", "createalbum"),
+            ("userhash", "secret"),
+            ("title", "gateway"),
+            ("desc", ""),
+            ("files", &slug),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+
+    let response = http
+        .post(&api)
+        .bearer_auth("secret")
+        .form(&[("reqtype", "deletefiles"), ("files", &slug)])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    gateway.kill().unwrap();
+    gateway.wait().unwrap();
+
+    let state = server.state();
+    assert_eq!(state.file(&slug).unwrap().bytes, b"through the gateway");
+    assert_eq!(state.files[1].name, "cat.png");
+    assert_eq!(state.file_url(&state.files[1].slug), remote_url);
+    let album = state.albums.values().next().unwrap();
+    assert_eq!(
+        (album.title.as_str(), album.files.as_slice()),
+        ("gateway", [slug].as_slice())
+    );
+
+    let history = fs::read_to_string(server.history_path()).unwrap();
+    let entries = history
+        .lines()
+        .map(|x| serde_json::from_str::<serde_json::Value>(x).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().all(|x| x["source"] == "serve"));
+    assert_eq!(entries[0]["url"], file_url);
+    assert_eq!(entries[0]["size"], 19);
+    assert_eq!(entries[1]["url"], remote_url);
+    assert!(entries[1]["sha256"].is_null());
+}
+
+#[test]
+fn serve_needs_a_token_off_loopback() {
+    let server = MockServer::start();
+
+    let output = server
+        .cbx()
+        .args(["serve", "--bind", "0.0.0.0:0"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--token"));
+}
+
+#[test]
+fn album_export_formats() {
+    let server = MockServer::start();
+    let short = server.create_album("abc123", "reactions");
+    let image = server.seed_file("cat.png", b"png");
+    let video = server.seed_file("clip.mp4", b"mp4");
+    let notes = server.seed_file("notes, draft.txt", b"hello");
+    {
+        let mut state = server.state();
+        let slugs = state.files.iter().map(|x| x.slug.clone()).collect();
+        state.albums.get_mut(&short).unwrap().files = slugs;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let gallery = dir.path().join("gallery.html");
+
+    server.run(&[
+        "album",
+        "export",
+        "--album",
+        &short,
+        "--out",
+        gallery.to_str().unwrap(),
+    ]);
+    let html = fs::read_to_string(&gallery).unwrap();
+    assert!(html.contains("<title>reactions</title>"));
+    assert!(html.contains(&format!(r#"<img src="{image}" alt="cat.png""#)));
+    assert!(html.contains(&format!(r#"<video src="{video}""#)));
+    assert!(html.contains(&format!(
+        r#"<a href="{notes}" download>notes, draft.txt</a> <span>5 B</span>"#
+    )));
+
+    let m3u = server.run(&["album", "export", "--album", &short, "--format", "m3u"]);
+    assert_eq!(
+        String::from_utf8(m3u.stdout).unwrap(),
+        format!("#EXTM3U\n#EXTINF:-1,clip.mp4\n{video}\n")
+    );
+
+    let csv = server.run(&["album", "export", "--album", &short, "--format", "csv"]);
+    let csv = String::from_utf8(csv.stdout).unwrap();
+    let lines = csv.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], "url,name,size,date,short,extension");
+    assert_eq!(
+        lines[3],
+        format!("{notes},\"notes, draft.txt\",5,2024-01-04 12:00:00,000003,txt")
+    );
+
+    // public albums can be exported without an account, just without names and sizes
+    let output = server
+        .cbx()
+        .env_remove("CBX_USERNAME")
+        .env_remove("CBX_PASSWORD")
+        .args(["album", "export", "--album", &short, "--format", "csv"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let csv = String::from_utf8(output.stdout).unwrap();
+    let lines = csv.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[3].starts_with(&format!("{notes},,")));
+
+    // line breaks in names can't add entries to playlists
+    server
+        .state()
+        .files
+        .iter_mut()
+        .find(|x| x.name == "clip.mp4")
+        .unwrap()
+        .name = "clip\r\n#EXTINF:-1,evil.mp4".to_owned();
+    let m3u = server.run(&[
+        "--refresh",
+        "album",
+        "export",
+        "--album",
+        &short,
+        "--format",
+        "m3u",
+    ]);
+    assert_eq!(
+        String::from_utf8(m3u.stdout).unwrap(),
+        format!("#EXTM3U\n#EXTINF:-1,clip  #EXTINF:-1,evil.mp4\n{video}\n")
+    );
+}
+
+#[test]
+fn adding_foreign_file_to_album_fails() {
+    let server = MockServer::start();
+    let short = server.create_album("abc123", "reactions");
+
+    let output = server
+        .cbx()
+        .args(["album", "add", "--album", &short, "zzzzzz.png"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(server.state().albums[&short].files.is_empty());
+}
+
+#[test]
+fn album_upload_adds_new_files() {
+    let server = MockServer::start();
+    let short = server.create_album("abc123", "reactions");
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("wave.gif");
+    fs::write(&path, b"gif").unwrap();
+
+    server.run(&["album", "upload", "--album", &short, path.to_str().unwrap()]);
+
+    let state = server.state();
+    assert_eq!(state.files.len(), 1);
+    assert_eq!(state.albums[&short].files, [state.files[0].slug.clone()]);
+}
+
+#[test]
+fn directory_upload_is_archived() {
+    let server = MockServer::start();
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("assets");
+    fs::create_dir_all(root.join("nested")).unwrap();
+    fs::write(root.join("a.txt"), "a").unwrap();
+    fs::write(root.join("nested/b.txt"), "b").unwrap();
+    fs::write(root.join("skip.log"), "log").unwrap();
+
+    server.run(&[
+        "file",
+        "upload",
+        "--exclude",
+        "*.log",
+        root.to_str().unwrap(),
+    ]);
+
+    let state = server.state();
+    assert_eq!(state.files.len(), 1);
+    assert_eq!(state.files[0].name, "assets.tar.zst");
+
+    let decoder = zstd::stream::read::Decoder::new(state.files[0].bytes.as_slice()).unwrap();
+    let mut archive = tar::Archive::new(decoder);
+    let mut entries = archive
+        .entries()
+        .unwrap()
+        .map(|x| {
+            let mut entry = x.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().into_owned();
+            let mut content = String::new();
+            entry.read_to_string(&mut content).unwrap();
+            (path, content)
+        })
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    assert_eq!(
+        entries,
+        [
+            ("assets/a.txt".to_owned(), "a".to_owned()),
+            ("assets/nested/b.txt".to_owned(), "b".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn recursive_upload_collects_into_album() {
+    let server = MockServer::start();
+    let short = server.create_album("abc123", "assets");
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("nested")).unwrap();
+    fs::write(dir.path().join("a.txt"), "a").unwrap();
+    fs::write(dir.path().join("nested/b.txt"), "b").unwrap();
+
+    server.run(&[
+        "file",
+        "upload",
+        "--recursive",
+        "--album",
+        &short,
+        dir.path().to_str().unwrap(),
+    ]);
+
+    let state = server.state();
+    let mut names = state
+        .files
+        .iter()
+        .map(|x| x.name.as_str())
+        .collect::<Vec<_>>();
+    names.sort_unstable();
+    assert_eq!(names, ["a.txt", "b.txt"]);
+    assert_eq!(state.albums[&short].files.len(), 2);
+}
+
+#[test]
+fn strip_metadata_removes_png_text_chunks() {
+    let server = MockServer::start();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("screenshot.png");
+
+    let ihdr = png_chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+    let idat = png_chunk(b"IDAT", &[1, 2, 3]);
+    let iend = png_chunk(b"IEND", &[]);
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png.extend_from_slice(&ihdr);
+    png.extend_from_slice(&png_chunk(b"tEXt", b"Location\0secret place"));
+    png.extend_from_slice(&idat);
+    png.extend_from_slice(&png_chunk(b"eXIf", b"MM\0*"));
+    png.extend_from_slice(&iend);
+    fs::write(&path, &png).unwrap();
+
+    server.run(&["file", "upload", "--strip-metadata", path.to_str().unwrap()]);
+
+    let mut expected = b"\x89PNG\r\n\x1a\n".to_vec();
+    expected.extend_from_slice(&ihdr);
+    expected.extend_from_slice(&idat);
+    expected.extend_from_slice(&iend);
+    assert_eq!(server.state().files[0].bytes, expected);
+}
@@ -0,0 +1,201 @@
+mod common;
+
+use std::sync::Arc;
+
+use catbox::{
+    endpoints::Endpoints,
+    progress::{ChannelReporter, ProgressEvent, TaskKind},
+    upload::UploadOptions,
+    CatboxClient, UserError,
+};
+use common::{MockServer, PASSWORD, USERNAME};
+use reqwest::Url;
+
+fn client(server: &MockServer) -> CatboxClient {
+    CatboxClient::builder()
+        .endpoints(Endpoints::from_base_url(Url::parse(&server.url).unwrap()))
+        .credentials(USERNAME, PASSWORD)
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn upload_list_and_download() {
+    let server = MockServer::start();
+    let client = client(&server);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("hello.txt");
+    std::fs::write(&path, "hello").unwrap();
+
+    let uploaded = client
+        .upload(&path, &UploadOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(uploaded.size, 5);
+    // sha256 of "hello"
+    assert_eq!(
+        uploaded.sha256,
+        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+    );
+    let url = uploaded.url;
+
+    let files = client.list_files().await.unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].url, Url::parse(&url).unwrap());
+    assert_eq!(files[0].name.as_deref(), Some("hello.txt"));
+    assert_eq!(files[0].size, Some(5));
+    assert_eq!(files[0].slug(), client.file_slug(&url));
+
+    let target = dir.path().join("downloaded.txt");
+    let written = client
+        .download(Url::parse(&url).unwrap(), &target)
+        .await
+        .unwrap();
+    assert_eq!(written, 5);
+    assert_eq!(std::fs::read_to_string(target).unwrap(), "hello");
+
+    // a download that can't be moved into place leaves nothing behind
+    let occupied = dir.path().join("occupied");
+    std::fs::create_dir_all(occupied.join("inside")).unwrap();
+    assert!(client
+        .download(Url::parse(&url).unwrap(), &occupied)
+        .await
+        .is_err());
+    let mut left = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|x| x.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    left.sort();
+    assert_eq!(left, ["downloaded.txt", "hello.txt", "occupied"]);
+}
+
+#[tokio::test]
+async fn album_lifecycle() {
+    let server = MockServer::start();
+    let client = client(&server);
+    let first = server.seed_file("first.png", b"1");
+    let second = server.seed_file("second.png", b"2");
+    let first_slug = client.file_slug(&first);
+    let second_slug = client.file_slug(&second);
+
+    let album = client
+        .create_album("reactions", "faces", &[&first_slug])
+        .await
+        .unwrap();
+    let albums = client.list_albums().await.unwrap();
+    assert_eq!(albums.len(), 1);
+    assert_eq!(albums[0].url, album.url);
+    assert_eq!(albums[0].title.as_deref(), Some("reactions"));
+    assert_eq!(albums[0].description.as_deref(), Some("faces"));
+    assert_eq!(albums[0].file_count, Some(1));
+    assert!(albums[0].created.is_some());
+
+    client.add_to_album(&album, &second_slug).await.unwrap();
+    let (fetched, files) = client.fetch_album(&album).await.unwrap();
+    assert_eq!(fetched.title.as_deref(), Some("reactions"));
+    assert_eq!(fetched.description.as_deref(), Some("faces"));
+    assert_eq!(fetched.file_count, Some(2));
+    assert_eq!(files.files.len(), 2);
+
+    client
+        .remove_from_album(&album, &[&first_slug])
+        .await
+        .unwrap();
+    let files = client.album_files(&album).await.unwrap();
+    assert_eq!(
+        files.urls().collect::<Vec<_>>(),
+        [&Url::parse(&second).unwrap()]
+    );
+
+    client.delete_album(&album).await.unwrap();
+    assert!(client.list_albums().await.unwrap().is_empty());
+
+    client.delete_files(&[&second_slug]).await.unwrap();
+    assert_eq!(client.list_files().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn missing_credentials_are_reported() {
+    let server = MockServer::start();
+    let client = CatboxClient::builder()
+        .endpoints(Endpoints::from_base_url(Url::parse(&server.url).unwrap()))
+        .build()
+        .unwrap();
+
+    assert!(matches!(
+        client.list_files().await,
+        Err(UserError::LackOfCredentials)
+    ));
+}
+
+#[tokio::test]
+async fn upload_reports_progress() {
+    let server = MockServer::start();
+    let client = client(&server);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("hello.txt");
+    std::fs::write(&path, "hello").unwrap();
+
+    // log in first, so the only task reported is the upload
+    client.user().await.unwrap();
+
+    let (reporter, mut events) = ChannelReporter::new();
+    client
+        .with_progress(Arc::new(reporter))
+        .upload(&path, &UploadOptions::default())
+        .await
+        .unwrap();
+
+    let mut received = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        received.push(event);
+    }
+
+    let [ProgressEvent::Started {
+        task,
+        kind,
+        label,
+        total,
+    }, ProgressEvent::BytesSent { bytes, .. }, ProgressEvent::Finished { task: finished }] =
+        received.as_slice()
+    else {
+        panic!("unexpected events: {received:?}");
+    };
+    assert_eq!(label, &path.display().to_string());
+    assert_eq!(*kind, TaskKind::Upload);
+    assert_eq!(*total, Some(5));
+    assert_eq!(*bytes, 5);
+    assert_eq!(task, finished);
+}
+
+#[tokio::test]
+async fn download_reports_progress() {
+    let server = MockServer::start();
+    let url = server.seed_file("hello.txt", b"hello");
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("hello.txt");
+
+    let (reporter, mut events) = ChannelReporter::new();
+    client(&server)
+        .with_progress(Arc::new(reporter))
+        .download(Url::parse(&url).unwrap(), &path)
+        .await
+        .unwrap();
+
+    let mut received = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        received.push(event);
+    }
+
+    let [ProgressEvent::Started {
+        kind, label, total, ..
+    }, ProgressEvent::BytesSent { bytes, .. }, ProgressEvent::Finished { .. }] =
+        received.as_slice()
+    else {
+        panic!("unexpected events: {received:?}");
+    };
+    assert_eq!(label, &path.display().to_string());
+    assert_eq!(*kind, TaskKind::Download);
+    assert_eq!(*total, Some(5));
+    assert_eq!(*bytes, 5);
+}
@@ -0,0 +1,549 @@
+//! In-process mock of the catbox.moe pages and apis `cbx` talks to.
+//!
+//! Everything is served from a single host, files are stored in memory.
+
+#![allow(dead_code)]
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use axum::{
+    extract::{FromRequest, Multipart, Path as UrlPath, Query, Request, State as AxumState},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Form, Router,
+};
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+
+pub const USERNAME: &str = "kyle";
+pub const PASSWORD: &str = "hunter2";
+pub const USER_HASH: &str = "0123456789abcdef0123456789";
+const SESSION_COOKIE: &str = "PHPSESSID=mock-session";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredFile {
+    pub slug: String,
+    pub name: String,
+    pub bytes: Vec<u8>,
+    /// `time` for Litterbox uploads, `None` for catbox
+    pub expiry: Option<String>,
+    pub date: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StoredAlbum {
+    pub title: String,
+    pub description: String,
+    pub files: Vec<String>,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Debug, Default)]
+pub struct State {
+    base: String,
+    counter: usize,
+    /// files owned by the mock user, in upload order
+    pub files: Vec<StoredFile>,
+    /// anonymous Litterbox uploads
+    pub litter: Vec<StoredFile>,
+    pub albums: BTreeMap<String, StoredAlbum>,
+    /// files per page of the view page, everything is on one page when `None`
+    pub page_size: Option<usize>,
+    /// pages linked before and after the current one, every page is linked when `None`
+    pub page_window: Option<usize>,
+    /// uploads to the account are refused with a server error
+    pub reject_uploads: bool,
+}
+
+impl State {
+    /// Upload date of the latest file, one day after the previous one.
+    fn date(&self) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+            + TimeDelta::days(self.counter as i64)
+    }
+
+    fn next_slug(&mut self, name: &str) -> String {
+        self.counter += 1;
+        match Path::new(name).extension() {
+            Some(ext) => format!("{:06x}.{}", self.counter, ext.to_string_lossy()),
+            None => format!("{:06x}", self.counter),
+        }
+    }
+
+    pub fn file_url(&self, slug: &str) -> String {
+        format!("{}/{slug}", self.base)
+    }
+
+    pub fn album_url(&self, short: &str) -> String {
+        format!("{}/c/{short}", self.base)
+    }
+
+    pub fn file(&self, slug: &str) -> Option<&StoredFile> {
+        self.files.iter().find(|x| x.slug == slug)
+    }
+}
+
+type Shared = Arc<Mutex<State>>;
+
+pub struct MockServer {
+    pub url: String,
+    state: Shared,
+    config_dir: tempfile::TempDir,
+}
+
+impl MockServer {
+    /// Starts the server on a random port, running on its own thread until the process exits.
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+        listener
+            .set_nonblocking(true)
+            .expect("nonblocking listener");
+        let url = format!("http://{}", listener.local_addr().expect("local addr"));
+
+        let state = Arc::new(Mutex::new(State {
+            base: url.clone(),
+            ..State::default()
+        }));
+
+        let app = router(state.clone());
+
+        std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("mock runtime")
+                .block_on(async move {
+                    let listener =
+                        tokio::net::TcpListener::from_std(listener).expect("tokio listener");
+                    axum::serve(listener, app).await.expect("mock server");
+                });
+        });
+
+        Self {
+            url,
+            state,
+            config_dir: tempfile::tempdir().expect("config dir"),
+        }
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("poisoned mock state")
+    }
+
+    /// Adds an album owned by the mock user, returning its short.
+    pub fn create_album(&self, short: &str, title: &str) -> String {
+        let mut state = self.state();
+        let created = state.date();
+        state.albums.insert(
+            short.to_owned(),
+            StoredAlbum {
+                title: title.to_owned(),
+                created,
+                ..StoredAlbum::default()
+            },
+        );
+        short.to_owned()
+    }
+
+    /// Adds a file owned by the mock user, returning its url.
+    pub fn seed_file(&self, name: &str, bytes: &[u8]) -> String {
+        let mut state = self.state();
+        let slug = state.next_slug(name);
+        let date = state.date();
+        state.files.push(StoredFile {
+            slug: slug.clone(),
+            name: name.to_owned(),
+            bytes: bytes.to_vec(),
+            expiry: None,
+            date,
+        });
+        state.file_url(&slug)
+    }
+
+    /// Config file of every `cbx` invocation of this server.
+    pub fn config_path(&self) -> PathBuf {
+        self.config_dir.path().join("config.json")
+    }
+
+    /// Upload history of every `cbx` invocation of this server.
+    pub fn history_path(&self) -> PathBuf {
+        self.config_dir.path().join("history.jsonl")
+    }
+
+    /// A `cbx` invocation pointed at this server, logged in as the mock user.
+    pub fn cbx(&self) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_cbx"));
+        command
+            .args(["--base-url", &self.url, "--proxy", "none"])
+            .env("CBX_CONFIG", self.config_path())
+            .env("CBX_HISTORY", self.history_path())
+            .env("CBX_CACHE", self.config_dir.path().join("listing.json"))
+            .env("CBX_USERNAME", USERNAME)
+            .env("CBX_PASSWORD", PASSWORD);
+        command
+    }
+
+    /// Runs `cbx` with `args`, panicking with its output if it fails.
+    pub fn run(&self, args: &[&str]) -> Output {
+        let output = self.cbx().args(args).output().expect("spawn cbx");
+        assert!(
+            output.status.success(),
+            "cbx {args:?} failed\nstdout: {}\nstderr: {}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    }
+}
+
+fn router(state: Shared) -> Router {
+    Router::new()
+        .route("/user/dologin.php", post(login))
+        .route("/user/manage.php", get(manage))
+        .route("/user/view.php", get(view))
+        .route("/user/manage_albums.php", get(manage_albums))
+        .route("/user/api.php", post(api))
+        .route("/resources/internals/api.php", post(litter_api))
+        .route("/c/{short}", get(album))
+        .route("/{slug}", get(download))
+        .with_state(state)
+}
+
+fn fixture(name: &str) -> &'static str {
+    match name {
+        "manage" => include_str!("../fixtures/manage.html"),
+        "view" => include_str!("../fixtures/view.html"),
+        "manage_albums" => include_str!("../fixtures/manage_albums.html"),
+        "album" => include_str!("../fixtures/album.html"),
+        _ => unreachable!("unknown fixture {name}"),
+    }
+}
+
+fn render(name: &str, values: &[(&str, &str)]) -> Html<String> {
+    let mut html = fixture(name).to_owned();
+    for (key, value) in values {
+        html = html.replace(&format!("{{{{{key}}}}}"), value);
+    }
+    Html(html)
+}
+
+fn logged_in(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|x| x.to_str().ok())
+        .any(|x| x.split("; ").any(|x| x == SESSION_COOKIE))
+}
+
+fn error(code: StatusCode, message: &str) -> Response {
+    (code, message.to_owned()).into_response()
+}
+
+async fn login(Form(form): Form<HashMap<String, String>>) -> Response {
+    let valid = form.get("username").map(String::as_str) == Some(USERNAME)
+        && form.get("password").map(String::as_str) == Some(PASSWORD);
+    if !valid {
+        return error(StatusCode::UNAUTHORIZED, "Invalid username or password.");
+    }
+    (
+        [(header::SET_COOKIE, format!("{SESSION_COOKIE}; Path=/"))],
+        "Logged in.",
+    )
+        .into_response()
+}
+
+async fn manage(AxumState(state): AxumState<Shared>, headers: HeaderMap) -> Response {
+    if !logged_in(&headers) {
+        return error(StatusCode::FORBIDDEN, "Not logged in.");
+    }
+    let count = state.lock().unwrap().files.len().to_string();
+    render("manage", &[("userhash", USER_HASH), ("count", &count)]).into_response()
+}
+
+async fn view(
+    AxumState(state): AxumState<Shared>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    if !logged_in(&headers) {
+        return error(StatusCode::FORBIDDEN, "Not logged in.");
+    }
+    let state = state.lock().unwrap();
+    let page_size = state.page_size.unwrap_or(usize::MAX);
+    let page = query
+        .get("page")
+        .and_then(|x| x.parse::<usize>().ok())
+        .unwrap_or(1)
+        .max(1);
+    let page_count = state.files.len().div_ceil(page_size).max(1);
+
+    let files = state
+        .files
+        .iter()
+        .skip((page - 1).saturating_mul(page_size))
+        .take(page_size)
+        .map(|x| {
+            format!(
+                "\t\t<tr><td><a href=\"{}\" target=\"_blank\">{}</a></td><td>{}</td><td>{} B</td><td>{}</td></tr>",
+                state.file_url(&x.slug),
+                x.slug,
+                x.name,
+                x.bytes.len(),
+                x.date.format("%Y-%m-%d %H:%M:%S"),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let window = state.page_window.unwrap_or(usize::MAX);
+    let pagination = (page.saturating_sub(window).max(1)
+        ..=page.saturating_add(window).min(page_count))
+        .map(|x| format!("<a href=\"view.php?page={x}\">{x}</a>"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    render("view", &[("files", &files), ("pagination", &pagination)]).into_response()
+}
+
+async fn manage_albums(AxumState(state): AxumState<Shared>, headers: HeaderMap) -> Response {
+    if !logged_in(&headers) {
+        return error(StatusCode::FORBIDDEN, "Not logged in.");
+    }
+    let state = state.lock().unwrap();
+    let albums = state
+        .albums
+        .iter()
+        .map(|(short, album)| {
+            format!(
+                "\t\t<tr><td><span class=\"textHolder\">{}</span></td><td>{}</td><td>{}</td><td>{}</td><td>{} files</td></tr>",
+                state.album_url(short),
+                album.title,
+                album.description,
+                album.created.format("%Y-%m-%d %H:%M:%S"),
+                album.files.len(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    render("manage_albums", &[("albums", &albums)]).into_response()
+}
+
+async fn album(AxumState(state): AxumState<Shared>, UrlPath(short): UrlPath<String>) -> Response {
+    let state = state.lock().unwrap();
+    let Some(album) = state.albums.get(&short) else {
+        return error(StatusCode::NOT_FOUND, "No such album.");
+    };
+    let files = album
+        .files
+        .iter()
+        .map(|slug| {
+            let url = state.file_url(slug);
+            match Path::new(slug).extension().and_then(|x| x.to_str()) {
+                Some("png" | "jpg" | "jpeg" | "gif" | "webp") => {
+                    format!("\t\t<img src=\"{url}\">")
+                }
+                Some("mp4" | "webm") => format!("\t\t<video src=\"{url}\" controls></video>"),
+                _ => format!("\t\t<a href=\"{url}\">{slug}</a>"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    render(
+        "album",
+        &[
+            ("title", &album.title),
+            ("description", &album.description),
+            ("files", &files),
+        ],
+    )
+    .into_response()
+}
+
+async fn download(AxumState(state): AxumState<Shared>, UrlPath(slug): UrlPath<String>) -> Response {
+    let state = state.lock().unwrap();
+    state
+        .files
+        .iter()
+        .chain(&state.litter)
+        .find(|x| x.slug == slug)
+        .map_or_else(
+            || error(StatusCode::NOT_FOUND, "No such file."),
+            |x| x.bytes.clone().into_response(),
+        )
+}
+
+/// Collects the text fields and the uploaded file of a multipart body.
+async fn read_multipart(
+    mut multipart: Multipart,
+) -> (HashMap<String, String>, Option<(String, Vec<u8>)>) {
+    let mut fields = HashMap::new();
+    let mut file = None;
+    while let Some(field) = multipart.next_field().await.expect("multipart field") {
+        let name = field.name().unwrap_or_default().to_owned();
+        if let Some(file_name) = field.file_name().map(ToOwned::to_owned) {
+            file = Some((file_name, field.bytes().await.expect("file bytes").to_vec()));
+        } else {
+            fields.insert(name, field.text().await.expect("text field"));
+        }
+    }
+    (fields, file)
+}
+
+async fn api(AxumState(state): AxumState<Shared>, request: Request) -> Response {
+    let is_multipart = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok())
+        .is_some_and(|x| x.starts_with("multipart/form-data"));
+
+    let (fields, file) = if is_multipart {
+        read_multipart(
+            Multipart::from_request(request, &())
+                .await
+                .expect("multipart"),
+        )
+        .await
+    } else {
+        let Form(fields) = Form::<HashMap<String, String>>::from_request(request, &())
+            .await
+            .expect("form");
+        (fields, None)
+    };
+
+    let field = |name: &str| fields.get(name).map(String::as_str).unwrap_or_default();
+
+    if field("userhash") != USER_HASH {
+        return error(StatusCode::PRECONDITION_FAILED, "Invalid userhash.");
+    }
+
+    let mut state = state.lock().unwrap();
+
+    match field("reqtype") {
+        "fileupload" => {
+            let Some((name, bytes)) = file else {
+                return error(StatusCode::BAD_REQUEST, "No files given.");
+            };
+            if state.reject_uploads {
+                return error(StatusCode::INTERNAL_SERVER_ERROR, "Upload rejected.");
+            }
+            let slug = state.next_slug(&name);
+            let date = state.date();
+            state.files.push(StoredFile {
+                slug: slug.clone(),
+                name,
+                bytes,
+                expiry: None,
+                date,
+            });
+            state.file_url(&slug).into_response()
+        }
+        "urlupload" => {
+            let Some(name) = field("url")
+                .rsplit('/')
+                .next()
+                .filter(|x| !x.is_empty())
+                .map(ToOwned::to_owned)
+            else {
+                return error(StatusCode::BAD_REQUEST, "Invalid url.");
+            };
+            let slug = state.next_slug(&name);
+            let date = state.date();
+            state.files.push(StoredFile {
+                slug: slug.clone(),
+                name,
+                bytes: Vec::new(),
+                expiry: None,
+                date,
+            });
+            state.file_url(&slug).into_response()
+        }
+        "addtoalbum" | "removefromalbum" => {
+            let short = field("short").to_owned();
+            let files = field("files")
+                .split_whitespace()
+                .map(ToOwned::to_owned)
+                .collect::<Vec<_>>();
+            if files.iter().any(|x| state.file(x).is_none()) {
+                return error(StatusCode::BAD_REQUEST, "File does not belong to you.");
+            }
+            let Some(album) = state.albums.get_mut(&short) else {
+                return error(StatusCode::BAD_REQUEST, "No such album.");
+            };
+            if field("reqtype") == "addtoalbum" {
+                for file in files {
+                    if !album.files.contains(&file) {
+                        album.files.push(file);
+                    }
+                }
+            } else {
+                album.files.retain(|x| !files.contains(x));
+            }
+            "Success".into_response()
+        }
+        "createalbum" => {
+            state.counter += 1;
+            let short = format!("al{:04x}", state.counter);
+            let files = field("files")
+                .split_whitespace()
+                .map(ToOwned::to_owned)
+                .collect();
+            let created = state.date();
+            state.albums.insert(
+                short.clone(),
+                StoredAlbum {
+                    title: field("title").to_owned(),
+                    description: field("desc").to_owned(),
+                    files,
+                    created,
+                },
+            );
+            state.album_url(&short).into_response()
+        }
+        "deletealbum" => match state.albums.remove(field("short")) {
+            Some(_) => "Success".into_response(),
+            None => error(StatusCode::BAD_REQUEST, "No such album."),
+        },
+        "deletefiles" => {
+            let files = field("files").split_whitespace().collect::<Vec<_>>();
+            state.files.retain(|x| !files.contains(&x.slug.as_str()));
+            for album in state.albums.values_mut() {
+                album.files.retain(|x| !files.contains(&x.as_str()));
+            }
+            "Files successfully deleted.".into_response()
+        }
+        reqtype => error(
+            StatusCode::BAD_REQUEST,
+            &format!("Unknown reqtype {reqtype}"),
+        ),
+    }
+}
+
+async fn litter_api(AxumState(state): AxumState<Shared>, multipart: Multipart) -> Response {
+    let (fields, file) = read_multipart(multipart).await;
+
+    if fields.get("reqtype").map(String::as_str) != Some("fileupload") {
+        return error(StatusCode::BAD_REQUEST, "Unknown reqtype.");
+    }
+    let Some((name, bytes)) = file else {
+        return error(StatusCode::BAD_REQUEST, "No files given.");
+    };
+
+    let mut state = state.lock().unwrap();
+    let slug = state.next_slug(&name);
+    let date = state.date();
+    state.litter.push(StoredFile {
+        slug: slug.clone(),
+        name,
+        bytes,
+        expiry: fields.get("time").cloned(),
+        date,
+    });
+    state.file_url(&slug).into_response()
+}